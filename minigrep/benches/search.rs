@@ -0,0 +1,112 @@
+//! Compares a line-by-line search, as the book's `search` was written,
+//! with `search_text`, which scans the whole buffer for a literal and only
+//! splits lines around hits.
+//!
+//! Run with `cargo bench`. The large inputs are 64 MiB by default; set
+//! `MINIGREP_BENCH_BYTES=1073741824` to search 1 GB instead.
+
+use std::env;
+use std::fs::{self, File};
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use memmap2::Mmap;
+use minigrep::{search_text, Config};
+
+const POEM: &str = include_str!("../poem.txt");
+
+fn config(query: &str) -> Config {
+    let args = ["minigrep", query, "-"].map(String::from);
+    Config::new(args.into_iter()).unwrap()
+}
+
+/// The lines of `text` that contain `query`, checking each line in turn.
+/// `search` itself now goes through `search_text`, so this is the baseline.
+fn by_line<'a>(query: &str, text: &'a str) -> Vec<&'a str> {
+    text.lines().filter(|line| line.contains(query)).collect()
+}
+
+/// How many of `query`'s lines `search_text` selects in `text`.
+fn count(config: &Config, text: &str) -> usize {
+    search_text(config, text, |line| {
+        black_box(line);
+        Ok(())
+    })
+    .unwrap()
+}
+
+/// Log-like lines adding up to about `bytes`, with `needle` on one line in
+/// every ten thousand.
+fn log(bytes: usize, needle: &str) -> String {
+    let mut text = String::with_capacity(bytes + 100);
+    let mut i = 0;
+
+    while text.len() < bytes {
+        if i % 10_000 == 0 {
+            text.push_str(&format!("{} ERROR disk {} is full\n", i, needle));
+        } else {
+            text.push_str(&format!("{} INFO request served in 12ms\n", i));
+        }
+        i += 1;
+    }
+
+    text
+}
+
+fn poem(c: &mut Criterion) {
+    let config = config("body");
+    let mut group = c.benchmark_group("poem");
+    group.throughput(Throughput::Bytes(POEM.len() as u64));
+
+    group.bench_function("by_line", |b| {
+        b.iter(|| by_line(black_box("body"), black_box(POEM)))
+    });
+    group.bench_function("search_text", |b| {
+        b.iter(|| count(&config, black_box(POEM)))
+    });
+    group.finish();
+}
+
+fn large(c: &mut Criterion) {
+    let bytes = env::var("MINIGREP_BENCH_BYTES")
+        .ok()
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(64 << 20);
+    let text = log(bytes, "sda1");
+    let config = config("sda1");
+
+    let path = env::temp_dir()
+        .join(format!("minigrep-bench-{}.log", std::process::id()));
+    fs::write(&path, &text).unwrap();
+
+    let mut group = c.benchmark_group("large");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    group.bench_function("by_line", |b| {
+        b.iter(|| by_line(black_box("sda1"), black_box(&text)))
+    });
+    group.bench_function("search_text", |b| {
+        b.iter(|| count(&config, black_box(&text)))
+    });
+    group.bench_function("read_to_string+by_line", |b| {
+        b.iter(|| {
+            let text = fs::read_to_string(&path).unwrap();
+            by_line("sda1", &text).len()
+        })
+    });
+    group.bench_function("mmap+search_text", |b| {
+        b.iter(|| {
+            let file = File::open(&path).unwrap();
+            // SAFETY: nothing else touches the file while it's mapped.
+            let map = unsafe { Mmap::map(&file).unwrap() };
+            count(&config, std::str::from_utf8(&map).unwrap())
+        })
+    });
+    group.finish();
+
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, poem, large);
+criterion_main!(benches);
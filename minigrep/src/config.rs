@@ -0,0 +1,1086 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::defaults::{self, Value};
+use crate::walk::Walker;
+use crate::Error;
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] [--] QUERY [PATH...]
+       minigrep [OPTIONS] -e PATTERN... [-f FILE...] [--] [PATH...]
+       minigrep --interactive [OPTIONS] FILE
+
+Search each PATH for lines containing QUERY, or any of the patterns given
+with -e and -f. If a PATH is a directory, every text file below it is
+searched, except hidden files and anything a .gitignore or .ignore file
+lists. With more than one file, each line is prefixed with its path. With
+no PATH, or when PATH is -, standard input is searched.
+
+Options:
+  -E, --regex             Treat QUERY and patterns as regular expressions
+  -e, --regexp PATTERN    Search for PATTERN; may be given more than once
+  -f, --file FILE         Search for every line of FILE, one pattern each
+  -i, --ignore-case       Match case-insensitively
+  -s, --case-sensitive    Match case-sensitively, overriding CASE_INSENSITIVE
+  -S, --smart-case        Match case-insensitively unless QUERY has uppercase
+  -w, --word-regexp       Only match whole words: no letter, digit or _
+                          may come just before or after a match
+  -x, --line-regexp       Only match whole lines
+  -U, --multiline         Let matches span lines, as in -E 'fn \\w+\\(\\n';
+                          each prints with every line it touches
+  -n, --line-number       Prefix each line with its line number
+  -v, --invert-match      Print the lines that do not match
+  -c, --count             Print only the number of selected lines
+  -m, --max-count N       Stop searching a file after N selected lines and
+                          the context after the last of them
+      --fuzzy N           Select lines with a part within N single-character
+                          edits of QUERY, closest first and without context
+                          unless -v is given
+  -A, --after-context N   Print N lines of context after each selected line
+  -B, --before-context N  Print N lines of context before each selected line
+  -C, --context N         Print N lines of context before and after
+      --color WHEN        Highlight matches: auto (the default), always, never
+      --json              Print JSON Lines: begin, match, context, end and
+                          summary records, for other tools to read
+  -z, --search-zip        Search inside gzip, xz and zstd compressed files,
+                          recognized by their contents rather than names
+  -r, --replace REPL      Print selected lines with each match replaced by
+                          REPL; with -E, $1 or ${name} in REPL stands for
+                          a capture group
+      --write             With -r, make the replacements in each file
+      --dry-run           With -r, print a unified diff of what --write
+                          would change, without changing anything
+  -g, --glob GLOB         Only search files below a directory that match
+                          GLOB, or with !GLOB, skip those that do; may be
+                          given more than once
+  -t, --type TYPE         Only search files of TYPE below a directory, such
+                          as rust, py or md; may be given more than once
+      --hidden            Search hidden files and directories too
+      --no-ignore         Search files that .gitignore and .ignore list
+  -j, --threads N         Search up to N files at once (default: one per CPU)
+      --interactive       Search FILE as a query is typed in a terminal UI;
+                          Enter prints the chosen line and its number
+      --debug-config      Print every setting and where it came from to
+                          stderr before searching
+  -h, --help              Print this help and exit
+  -V, --version           Print the version and exit
+  --                      Treat every following argument as QUERY or PATH
+
+Environment:
+  MINIGREP_CONFIG         A TOML file of defaults for options, one key per
+                          long option name: color = \"never\", context = 2,
+                          glob = [\"!target/**\"] and so on
+  MINIGREP_<OPTION>       A default for one option, overriding the file's:
+                          MINIGREP_COLOR=never, MINIGREP_SMART_CASE=true,
+                          MINIGREP_GLOB='!target/** !*.lock'
+  CASE_INSENSITIVE        When set, match case-insensitively unless -s or -S
+                          is given
+
+Defaults can be given for -E, -i, -s, -S, -n, -A, -B, -C, --color, -z, -g,
+-t, --hidden, --no-ignore and -j. The command line wins over the
+environment, which wins over the file.
+
+Exit status:
+  0 if a line was selected, 1 if none was, and 2 if an error occurred
+";
+
+pub struct Config {
+    /// What to search for: QUERY, or every `-e` pattern and line of each
+    /// `-f` file. A line matches if any one of them does.
+    pub patterns: Vec<String>,
+    /// The files and directories to search, in the order they were given;
+    /// `-` means standard input.
+    pub paths: Vec<String>,
+    pub case_sensitive: bool,
+    /// The compiled patterns when `-E`/`--regex` was given, built once here
+    /// so `run` never has to re-parse them.
+    pub regex: Option<Regex>,
+    /// Only match whole words, for `-w`.
+    pub word_regexp: bool,
+    /// Only match whole lines, for `-x`. Wins over `word_regexp`.
+    pub line_regexp: bool,
+    /// Search each input as a whole, so a match can span lines, for `-U`.
+    pub multiline: bool,
+    pub line_number: bool,
+    pub invert_match: bool,
+    pub count: bool,
+    /// Stop searching an input after this many selected lines.
+    pub max_count: Option<usize>,
+    pub before_context: usize,
+    pub after_context: usize,
+    /// Decides which files below a directory in `paths` get searched.
+    pub walker: Walker,
+    /// How many files to search at once.
+    pub threads: usize,
+    pub color: ColorChoice,
+    pub json: bool,
+    /// With `--fuzzy`, how many single-character edits a match can be from
+    /// the query. Lines are then printed closest first, unless
+    /// `invert_match` selects the lines that aren't within it.
+    pub fuzzy: Option<usize>,
+    /// Decompress gzip, xz and zstd inputs before searching them.
+    pub search_zip: bool,
+    /// What `--replace` puts in place of each match.
+    pub replace: Option<String>,
+    /// Edit files in place with `replace` instead of printing lines.
+    pub write: bool,
+    /// Print the edits `write` would make as a diff. Wins over `write`.
+    pub dry_run: bool,
+    /// Search the one file in `paths` from a terminal UI, starting from
+    /// any pattern given with `-e`, instead of printing every match.
+    pub interactive: bool,
+    /// Print `describe` before searching.
+    pub debug_config: bool,
+    /// Where each setting that isn't a built-in default came from, by the
+    /// name of the field it's in. `walker` settings go by `globs`, `types`,
+    /// `hidden` and `no_ignore`. Globs and types add up across places, so
+    /// theirs is the last place that added one.
+    pub origins: BTreeMap<&'static str, Origin>,
+}
+
+/// Where a setting came from, from lowest precedence to highest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Origin {
+    #[default]
+    BuiltIn,
+    /// The TOML file named by `MINIGREP_CONFIG`.
+    File(PathBuf),
+    /// An environment variable, by name.
+    Env(String),
+    CommandLine,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::BuiltIn => write!(f, "built-in default"),
+            Origin::File(path) => write!(f, "config file {}", path.display()),
+            Origin::Env(var) => write!(f, "environment variable {}", var),
+            Origin::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// When to highlight output with ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+/// What the command line asked for: a search, or one of the flags that
+/// print something and exit without searching.
+pub enum Command {
+    Search(Box<Config>),
+    Help,
+    Version,
+}
+
+impl Command {
+    /// Parse the command line, on top of the defaults from the config file
+    /// and environment variables.
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Command, Error> {
+        parse(args, |name| env::var(name).ok())
+    }
+}
+
+impl Config {
+    pub fn new(args: impl Iterator<Item = String>) -> Result<Config, Error> {
+        match Command::parse(args)? {
+            Command::Search(config) => Ok(*config),
+            Command::Help | Command::Version => Err(Error::NotASearch),
+        }
+    }
+
+    /// Every setting, one per line, with where it came from, for
+    /// `--debug-config`.
+    pub fn describe(&self) -> String {
+        let settings: [(&str, String); 26] = [
+            ("patterns", format!("{:?}", self.patterns)),
+            ("paths", format!("{:?}", self.paths)),
+            ("regex", self.regex.is_some().to_string()),
+            ("case_sensitive", self.case_sensitive.to_string()),
+            ("word_regexp", self.word_regexp.to_string()),
+            ("line_regexp", self.line_regexp.to_string()),
+            ("multiline", self.multiline.to_string()),
+            ("line_number", self.line_number.to_string()),
+            ("invert_match", self.invert_match.to_string()),
+            ("count", self.count.to_string()),
+            ("max_count", format!("{:?}", self.max_count)),
+            ("before_context", self.before_context.to_string()),
+            ("after_context", self.after_context.to_string()),
+            ("globs", format!("{:?}", self.walker.globs())),
+            ("types", format!("{:?}", self.walker.types())),
+            ("hidden", self.walker.hidden().to_string()),
+            ("no_ignore", (!self.walker.ignore_files()).to_string()),
+            ("threads", self.threads.to_string()),
+            ("color", format!("{:?}", self.color).to_lowercase()),
+            ("json", self.json.to_string()),
+            ("fuzzy", format!("{:?}", self.fuzzy)),
+            ("search_zip", self.search_zip.to_string()),
+            ("replace", format!("{:?}", self.replace)),
+            ("write", self.write.to_string()),
+            ("dry_run", self.dry_run.to_string()),
+            ("interactive", self.interactive.to_string()),
+        ];
+        let mut description = String::new();
+
+        for (name, value) in settings {
+            let origin = self.origins.get(name).cloned().unwrap_or_default();
+            let setting = format!("{} = {}", name, value);
+            // Writing to a String can't fail.
+            let _ = writeln!(description, "{:<32} ({})", setting, origin);
+        }
+
+        description
+    }
+}
+
+/// How -i, -s and -S asked for case to be treated; the last one given wins.
+#[derive(Clone, Copy)]
+enum Case {
+    Sensitive,
+    Insensitive,
+    Smart,
+}
+
+/// Flags seen so far; turned into a `Config` once every argument is read.
+#[derive(Default)]
+struct Options {
+    /// Where the options being applied now come from.
+    origin: Origin,
+    origins: BTreeMap<&'static str, Origin>,
+    debug_config: bool,
+    use_regex: bool,
+    patterns: Vec<String>,
+    pattern_given: bool,
+    case: Option<Case>,
+    word_regexp: bool,
+    line_regexp: bool,
+    multiline: bool,
+    line_number: bool,
+    invert_match: bool,
+    count: bool,
+    max_count: Option<usize>,
+    after_context: Option<usize>,
+    before_context: Option<usize>,
+    context: Option<usize>,
+    globs: Vec<String>,
+    types: Vec<String>,
+    hidden: bool,
+    no_ignore: bool,
+    threads: Option<usize>,
+    color: Option<ColorChoice>,
+    json: bool,
+    fuzzy: Option<usize>,
+    search_zip: bool,
+    replace: Option<String>,
+    write: bool,
+    dry_run: bool,
+    interactive: bool,
+    help: bool,
+    version: bool,
+}
+
+/// Whether the option `name`, short or long, takes a value.
+pub(crate) fn takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "e" | "regexp"
+            | "f"
+            | "file"
+            | "m"
+            | "max-count"
+            | "A"
+            | "after-context"
+            | "B"
+            | "before-context"
+            | "C"
+            | "context"
+            | "r"
+            | "replace"
+            | "g"
+            | "glob"
+            | "t"
+            | "type"
+            | "j"
+            | "threads"
+            | "color"
+            | "colour"
+            | "fuzzy"
+    )
+}
+
+/// The `Config` field the option `name`, short or long, sets, or `None`
+/// for the options that don't set one.
+fn field(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "E" | "regex" => "regex",
+        "e" | "regexp" | "f" | "file" => "patterns",
+        "i" | "ignore-case" | "s" | "case-sensitive" | "S" | "smart-case" => {
+            "case_sensitive"
+        }
+        "w" | "word-regexp" => "word_regexp",
+        "x" | "line-regexp" => "line_regexp",
+        "U" | "multiline" => "multiline",
+        "n" | "line-number" => "line_number",
+        "v" | "invert-match" => "invert_match",
+        "c" | "count" => "count",
+        "m" | "max-count" => "max_count",
+        "A" | "after-context" => "after_context",
+        "B" | "before-context" => "before_context",
+        "g" | "glob" => "globs",
+        "t" | "type" => "types",
+        "hidden" => "hidden",
+        "no-ignore" => "no_ignore",
+        "j" | "threads" => "threads",
+        "color" | "colour" => "color",
+        "json" => "json",
+        "fuzzy" => "fuzzy",
+        "z" | "search-zip" => "search_zip",
+        "r" | "replace" => "replace",
+        "write" => "write",
+        "dry-run" => "dry_run",
+        "interactive" => "interactive",
+        _ => return None,
+    })
+}
+
+impl Options {
+    /// Apply a default from the config file or the environment.
+    fn preset(&mut self, name: &str, value: Value) -> Result<(), Error> {
+        match value {
+            Value::Values(values) => {
+                for value in values {
+                    self.apply(name, Some(value))?;
+                }
+            }
+            Value::Flag(true) => self.apply(name, None)?,
+            // Back off, as if no default had been given.
+            Value::Flag(false) => {
+                match name {
+                    "regex" => self.use_regex = false,
+                    "ignore-case" | "case-sensitive" | "smart-case" => {
+                        self.case = None
+                    }
+                    "line-number" => self.line_number = false,
+                    "search-zip" => self.search_zip = false,
+                    "hidden" => self.hidden = false,
+                    "no-ignore" => self.no_ignore = false,
+                    _ => {}
+                }
+                if let Some(field) = field(name) {
+                    self.origins.insert(field, self.origin.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(
+        &mut self,
+        name: &str,
+        value: Option<String>,
+    ) -> Result<(), Error> {
+        if let Some(field) = field(name) {
+            self.origins.insert(field, self.origin.clone());
+        }
+
+        match name {
+            "E" | "regex" => self.use_regex = true,
+            "e" | "regexp" => {
+                self.patterns.extend(value);
+                self.pattern_given = true;
+            }
+            "f" | "file" => {
+                let path = value.unwrap_or_default();
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| Error::reading(path, e))?;
+                self.patterns.extend(contents.lines().map(String::from));
+                self.pattern_given = true;
+            }
+            "i" | "ignore-case" => self.case = Some(Case::Insensitive),
+            "s" | "case-sensitive" => self.case = Some(Case::Sensitive),
+            "S" | "smart-case" => self.case = Some(Case::Smart),
+            "w" | "word-regexp" => self.word_regexp = true,
+            "x" | "line-regexp" => self.line_regexp = true,
+            "U" | "multiline" => self.multiline = true,
+            "n" | "line-number" => self.line_number = true,
+            "v" | "invert-match" => self.invert_match = true,
+            "c" | "count" => self.count = true,
+            "m" | "max-count" => self.max_count = Some(number(name, value)?),
+            "A" | "after-context" => {
+                self.after_context = Some(number(name, value)?)
+            }
+            "B" | "before-context" => {
+                self.before_context = Some(number(name, value)?)
+            }
+            "C" | "context" => {
+                self.context = Some(number(name, value)?);
+                // -A and -B only win over a -C from the same place, so a
+                // -C on the command line beats -A from the config file.
+                for field in ["before_context", "after_context"] {
+                    if self.origins.get(field) != Some(&self.origin) {
+                        self.origins.insert(field, self.origin.clone());
+                        match field {
+                            "before_context" => self.before_context = None,
+                            _ => self.after_context = None,
+                        }
+                    }
+                }
+            }
+            "g" | "glob" => self.globs.extend(value),
+            "t" | "type" => self.types.extend(value),
+            "hidden" => self.hidden = true,
+            "no-ignore" => self.no_ignore = true,
+            "j" | "threads" => match number(name, value)? {
+                0 => {
+                    return Err(Error::InvalidValue {
+                        option: flag(name),
+                        value: "0".to_string(),
+                        expected: "a number of threads of at least 1",
+                    })
+                }
+                n => self.threads = Some(n),
+            },
+            "color" | "colour" => {
+                self.color = Some(match value.as_deref() {
+                    Some("auto") => ColorChoice::Auto,
+                    Some("always") => ColorChoice::Always,
+                    Some("never") => ColorChoice::Never,
+                    _ => {
+                        return Err(Error::InvalidValue {
+                            option: flag(name),
+                            value: value.unwrap_or_default(),
+                            expected: "auto, always or never",
+                        });
+                    }
+                })
+            }
+            "json" => self.json = true,
+            "fuzzy" => self.fuzzy = Some(number(name, value)?),
+            "z" | "search-zip" => self.search_zip = true,
+            "r" | "replace" => self.replace = value,
+            "write" => self.write = true,
+            "dry-run" => self.dry_run = true,
+            "interactive" => self.interactive = true,
+            "debug-config" => self.debug_config = true,
+            "h" | "help" => self.help = true,
+            "V" | "version" => self.version = true,
+            _ => return Err(Error::UnknownOption(flag(name))),
+        }
+
+        Ok(())
+    }
+}
+
+fn flag(name: &str) -> String {
+    if name.chars().count() == 1 {
+        format!("-{}", name)
+    } else {
+        format!("--{}", name)
+    }
+}
+
+fn number(name: &str, value: Option<String>) -> Result<usize, Error> {
+    let value = value.unwrap_or_default();
+    value.parse().map_err(|_| Error::InvalidValue {
+        option: flag(name),
+        value,
+        expected: "a number",
+    })
+}
+
+/// `patterns` as the one regular expression `-E` searches with, anchored to
+/// whole lines for `-x`.
+pub(crate) fn compile_regex(
+    patterns: &[String],
+    case_sensitive: bool,
+    line_regexp: bool,
+    multiline: bool,
+) -> Result<Regex, Error> {
+    let mut pattern = alternation(patterns);
+    if line_regexp {
+        pattern = format!("^(?:{})$", pattern);
+    }
+    // With -U, ^ and $ still match at the start and end of each line.
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .multi_line(multiline)
+        .build()
+        .map_err(Error::InvalidPattern)
+}
+
+/// One regular expression that matches wherever any of `patterns` does.
+fn alternation(patterns: &[String]) -> String {
+    match patterns {
+        [pattern] => pattern.clone(),
+        // A class that can never match, so no patterns select no lines.
+        [] => "[^\\s\\S]".to_string(),
+        _ => patterns
+            .iter()
+            .map(|pattern| format!("(?:{})", pattern))
+            .collect::<Vec<_>>()
+            .join("|"),
+    }
+}
+
+/// Whether `query` has an uppercase letter, for `--smart-case`. In a
+/// regular expression, a letter after `\` is part of an escape such as
+/// `\S` or `\W`, so it doesn't count.
+fn has_uppercase(query: &str, regex: bool) -> bool {
+    let mut chars = query.chars();
+
+    while let Some(c) = chars.next() {
+        if regex && c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Parse `args`, the whole command line, with `env` to look up
+/// environment variables.
+fn parse(
+    mut args: impl Iterator<Item = String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Command, Error> {
+    args.next();
+
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+
+    for setting in defaults::load(&env)? {
+        options.origin = setting.origin;
+        options
+            .preset(&setting.name, setting.value)
+            .map_err(|source| Error::Setting {
+                origin: options.origin.clone(),
+                source: Box::new(source),
+            })?;
+    }
+    options.origin = Origin::CommandLine;
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.extend(args.by_ref());
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+
+            let value = if takes_value(name) {
+                match inline.or_else(|| args.next()) {
+                    Some(value) => Some(value),
+                    None => return Err(Error::MissingValue(arg)),
+                }
+            } else if inline.is_some() {
+                return Err(Error::UnexpectedValue(flag(name)));
+            } else {
+                None
+            };
+
+            options.apply(name, value)?;
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            // Short flags can be bundled, so `-in` is `-i -n`, and a flag
+            // that takes a value may have it attached, as in `-C2`.
+            let bundle = &arg[1..];
+            for (i, c) in bundle.char_indices() {
+                let name = &bundle[i..i + c.len_utf8()];
+                if !takes_value(name) {
+                    options.apply(name, None)?;
+                    continue;
+                }
+
+                let rest = &bundle[i + c.len_utf8()..];
+                let value = if rest.is_empty() {
+                    args.next()
+                } else {
+                    Some(rest.to_string())
+                };
+                if value.is_none() {
+                    return Err(Error::MissingValue(flag(name)));
+                }
+                options.apply(name, value)?;
+                break;
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    if options.help {
+        return Ok(Command::Help);
+    }
+    if options.version {
+        return Ok(Command::Version);
+    }
+
+    if options.replace.is_none() {
+        if options.write {
+            return Err(Error::NeedsOption("--write", "--replace"));
+        }
+        if options.dry_run {
+            return Err(Error::NeedsOption("--dry-run", "--replace"));
+        }
+    }
+
+    // --write edits one line at a time.
+    if options.multiline && options.write {
+        return Err(Error::Conflict("--multiline", "--write"));
+    }
+    if options.multiline && options.dry_run {
+        return Err(Error::Conflict("--multiline", "--dry-run"));
+    }
+
+    // Files are edited as they are on disk, not decompressed. A default -z
+    // from the environment or config file is just ignored.
+    let zip_given = options.search_zip
+        && options.origins.get("search_zip") == Some(&Origin::CommandLine);
+    if zip_given && options.write {
+        return Err(Error::Conflict("--search-zip", "--write"));
+    }
+    if zip_given && options.dry_run {
+        return Err(Error::Conflict("--search-zip", "--dry-run"));
+    }
+
+    if options.fuzzy.is_some() && options.use_regex {
+        return Err(Error::Conflict("--fuzzy", "--regex"));
+    }
+
+    let mut positional = positional.into_iter();
+
+    // With -e or -f, every positional argument is a path, and with
+    // --interactive the query is typed in later.
+    let patterns = if options.pattern_given || options.interactive {
+        options.patterns
+    } else {
+        match positional.next() {
+            Some(arg) => {
+                options.origins.insert("patterns", Origin::CommandLine);
+                vec![arg]
+            }
+            None => return Err(Error::MissingQuery),
+        }
+    };
+
+    // Every line is within a query's length of matching it.
+    if let Some(distance) = options.fuzzy {
+        let shortest = patterns.iter().map(|p| p.chars().count()).min();
+        if shortest.is_some_and(|shortest| distance >= shortest) {
+            return Err(Error::InvalidValue {
+                option: "--fuzzy".to_string(),
+                value: distance.to_string(),
+                expected: "fewer edits than the query has characters",
+            });
+        }
+    }
+
+    let mut paths: Vec<String> = positional.collect();
+    // The UI reads keys from the terminal, so it can't search standard
+    // input too.
+    if options.interactive && (paths.len() != 1 || paths[0] == "-") {
+        return Err(Error::NeedsOption("--interactive", "FILE"));
+    }
+    if paths.is_empty() {
+        paths.push("-".to_string());
+    } else {
+        options.origins.insert("paths", Origin::CommandLine);
+    }
+
+    // The last of -i, -s and -S wins, and the command line's over any
+    // default.
+    let case_sensitive = match options.case {
+        Some(Case::Sensitive) => true,
+        Some(Case::Insensitive) => false,
+        Some(Case::Smart) => patterns
+            .iter()
+            .any(|pattern| has_uppercase(pattern, options.use_regex)),
+        None => true,
+    };
+
+    let regex = if options.use_regex {
+        Some(compile_regex(
+            &patterns,
+            case_sensitive,
+            options.line_regexp,
+            options.multiline,
+        )?)
+    } else {
+        None
+    };
+
+    let walker = Walker::new(
+        &options.globs,
+        &options.types,
+        options.hidden,
+        !options.no_ignore,
+    )?;
+
+    // Like grep, -A and -B take precedence over a -C given alongside them.
+    let context = options.context.unwrap_or(0);
+
+    Ok(Command::Search(Box::new(Config {
+        patterns,
+        paths,
+        case_sensitive,
+        regex,
+        word_regexp: options.word_regexp,
+        line_regexp: options.line_regexp,
+        multiline: options.multiline,
+        line_number: options.line_number,
+        invert_match: options.invert_match,
+        count: options.count,
+        max_count: options.max_count,
+        before_context: options.before_context.unwrap_or(context),
+        after_context: options.after_context.unwrap_or(context),
+        walker,
+        threads: options.threads.unwrap_or_else(|| {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }),
+        color: options.color.unwrap_or(ColorChoice::Auto),
+        json: options.json,
+        fuzzy: options.fuzzy,
+        search_zip: options.search_zip,
+        replace: options.replace,
+        write: options.write,
+        dry_run: options.dry_run,
+        interactive: options.interactive,
+        debug_config: options.debug_config,
+        origins: options.origins,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{args, temp_path};
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// Parse `args` as a search, with `CASE_INSENSITIVE` set if `env` is.
+    fn search(args: impl Iterator<Item = String>, env: bool) -> Config {
+        let env = move |name: &str| {
+            (env && name == "CASE_INSENSITIVE").then(String::new)
+        };
+        search_with(args, env)
+    }
+
+    fn search_with(
+        args: impl Iterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Config {
+        match parse(args, env) {
+            Ok(Command::Search(config)) => *config,
+            _ => panic!("expected a search command"),
+        }
+    }
+
+    #[test]
+    fn flags_and_positionals() {
+        let config = search(args(&["-n", "to", "--count", "poem.txt"]), false);
+
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert!(config.line_number);
+        assert!(config.count);
+        assert!(!config.invert_match);
+        assert!(config.case_sensitive);
+    }
+
+    #[test]
+    fn bundled_short_flags() {
+        let config = search(args(&["-inv", "to", "poem.txt"]), false);
+
+        assert!(!config.case_sensitive);
+        assert!(config.line_number);
+        assert!(config.invert_match);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = search(args(&["--", "-v", "poem.txt"]), false);
+
+        assert_eq!(vec!["-v"], config.patterns);
+        assert!(!config.invert_match);
+    }
+
+    #[test]
+    fn missing_path_means_stdin() {
+        assert_eq!(vec!["-"], search(args(&["to"]), false).paths);
+    }
+
+    #[test]
+    fn several_paths_and_threads() {
+        let config = search(args(&["-j", "3", "to", "a.txt", "b"]), false);
+
+        assert_eq!(vec!["a.txt", "b"], config.paths);
+        assert_eq!(3, config.threads);
+        assert!(parse(args(&["--threads=0", "to", "a.txt"]), no_env).is_err());
+    }
+
+    #[test]
+    fn explicit_flag_overrides_env() {
+        assert!(!search(args(&["to", "poem.txt"]), true).case_sensitive);
+        assert!(search(args(&["-s", "to", "poem.txt"]), true).case_sensitive);
+        assert!(!search(args(&["-i", "to", "poem.txt"]), false).case_sensitive);
+    }
+
+    #[test]
+    fn patterns_from_flags_and_files() {
+        let path = temp_path("patterns");
+        fs::write(&path, "E042\r\ntimeout\n").unwrap();
+        let file = path.to_str().unwrap();
+
+        let result =
+            parse(args(&["-e", "disk", "-f", file, "app.log"]), no_env);
+        fs::remove_file(&path).unwrap();
+        let config = match result {
+            Ok(Command::Search(config)) => *config,
+            _ => panic!("expected a search command"),
+        };
+
+        assert_eq!(vec!["disk", "E042", "timeout"], config.patterns);
+        assert_eq!(vec!["app.log"], config.paths);
+    }
+
+    #[test]
+    fn missing_pattern_file() {
+        assert!(matches!(
+            parse(args(&["-f", "/no/such/patterns", "x"]), no_env),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn regex_patterns_are_combined() {
+        let config = search(args(&["-E", "-e", "a+", "-e", "b|c", "x"]), false);
+        let regex = config.regex.unwrap();
+
+        assert!(regex.is_match("c"));
+        assert!(regex.is_match("aa"));
+        assert!(!regex.is_match("d"));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(!search(args(&["-S", "rust", "-"]), false).case_sensitive);
+        assert!(search(args(&["-S", "Rust", "-"]), false).case_sensitive);
+        assert!(!search(args(&["-SE", r"\Srust", "-"]), false).case_sensitive);
+        // The last of -i, -s and -S wins.
+        assert!(
+            !search(args(&["-S", "-i", "Rust", "-"]), false).case_sensitive
+        );
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(
+            parse(args(&["--help"]), no_env),
+            Ok(Command::Help)
+        ));
+        assert!(matches!(parse(args(&["-V"]), no_env), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn unknown_flag_is_an_error() {
+        let err = parse(args(&["--frobnicate", "to", "poem.txt"]), no_env)
+            .err()
+            .unwrap();
+
+        assert_eq!("unknown option '--frobnicate'", err.to_string());
+    }
+
+    #[test]
+    fn context_values() {
+        let config = search(args(&["-C2", "-A", "4", "to", "poem.txt"]), false);
+        assert_eq!(2, config.before_context);
+        assert_eq!(4, config.after_context);
+
+        let config = search(args(&["--before-context=1", "to", "x"]), false);
+        assert_eq!(1, config.before_context);
+        assert_eq!(0, config.after_context);
+    }
+
+    #[test]
+    fn color_choice() {
+        let config = search(args(&["--color=never", "to"]), false);
+        assert_eq!(ColorChoice::Never, config.color);
+
+        let config = search(args(&["--colour", "always", "to"]), false);
+        assert_eq!(ColorChoice::Always, config.color);
+
+        assert_eq!(ColorChoice::Auto, search(args(&["to"]), false).color);
+        assert!(parse(args(&["--color=sometimes", "to"]), no_env).is_err());
+    }
+
+    #[test]
+    fn replace_and_write() {
+        let config = search(args(&["-r", "$1", "--write", "to", "x"]), false);
+        assert_eq!(Some("$1".to_string()), config.replace);
+        assert!(config.write);
+        assert!(!config.dry_run);
+
+        assert!(matches!(
+            parse(args(&["--dry-run", "to", "x"]), no_env),
+            Err(Error::NeedsOption("--dry-run", "--replace"))
+        ));
+        assert!(matches!(
+            parse(args(&["-zr", "$1", "--write", "to", "x"]), no_env),
+            Err(Error::Conflict("--search-zip", "--write"))
+        ));
+        assert!(matches!(
+            parse(args(&["-z", "-r", "x", "--dry-run", "to", "x"]), no_env),
+            Err(Error::Conflict("--search-zip", "--dry-run"))
+        ));
+
+        let env = |name: &str| {
+            (name == "MINIGREP_SEARCH_ZIP").then(|| "true".to_string())
+        };
+        let config = search_with(args(&["-r", "x", "--write", "to", "x"]), env);
+        assert!(config.search_zip && config.write);
+    }
+
+    #[test]
+    fn fuzzy_distance() {
+        let config = search(args(&["--fuzzy", "2", "nobody", "x"]), false);
+        assert_eq!(Some(2), config.fuzzy);
+
+        assert!(parse(args(&["--fuzzy=3", "abc", "x"]), no_env).is_err());
+        assert!(matches!(
+            parse(args(&["-E", "--fuzzy=1", "abc", "x"]), no_env),
+            Err(Error::Conflict("--fuzzy", "--regex"))
+        ));
+    }
+
+    #[test]
+    fn words_lines_and_max_count() {
+        let config = search(args(&["-wxU", "-m", "3", "to", "x"]), false);
+        assert!(config.word_regexp && config.line_regexp && config.multiline);
+        assert_eq!(Some(3), config.max_count);
+
+        let config = search(args(&["-x", "-E", "-e", "a", "-e", "b"]), false);
+        assert_eq!("^(?:(?:a)|(?:b))$", config.regex.unwrap().as_str());
+
+        assert!(matches!(
+            parse(args(&["-U", "-r", "x", "--write", "to", "x"]), no_env),
+            Err(Error::Conflict("--multiline", "--write"))
+        ));
+        assert!(parse(args(&["-m", "many", "to"]), no_env).is_err());
+    }
+
+    #[test]
+    fn interactive_takes_one_file() {
+        let config = search(args(&["--interactive", "poem.txt"]), false);
+        assert!(config.interactive);
+        assert!(config.patterns.is_empty());
+        assert_eq!(vec!["poem.txt"], config.paths);
+
+        let config = search(args(&["--interactive", "-e", "to", "x"]), false);
+        assert_eq!(vec!["to"], config.patterns);
+
+        for given in [&[][..], &["-"], &["a", "b"]] {
+            let mut all = vec!["--interactive"];
+            all.extend(given);
+            assert!(matches!(
+                parse(args(&all), no_env),
+                Err(Error::NeedsOption("--interactive", "FILE"))
+            ));
+        }
+    }
+
+    #[test]
+    fn bad_globs_and_types() {
+        assert!(search(args(&["-g", "*.rs", "-t", "md", "to", "."]), false)
+            .paths
+            .contains(&".".to_string()));
+        assert!(matches!(
+            parse(args(&["--type=klingon", "to", "."]), no_env),
+            Err(Error::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            parse(args(&["--glob", "{a,b", "to", "."]), no_env),
+            Err(Error::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn command_line_beats_environment_beats_file() {
+        let path = temp_path("config.toml");
+        fs::write(
+            &path,
+            "color = \"always\"\nline-number = true\nbefore-context = 1\n\
+             ignore-case = true\nglob = \"!target/**\"\n",
+        )
+        .unwrap();
+        let file = path.to_str().unwrap().to_string();
+        let env = move |name: &str| match name {
+            "MINIGREP_CONFIG" => Some(file.clone()),
+            "MINIGREP_COLOR" => Some("never".to_string()),
+            "MINIGREP_LINE_NUMBER" => Some("0".to_string()),
+            _ => None,
+        };
+
+        let config = search_with(args(&["-C3", "-g", "*.rs", "to"]), &env);
+        let defaults = search_with(args(&["-s", "to"]), &env);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ColorChoice::Never, config.color);
+        assert!(!config.line_number);
+        assert!(!config.case_sensitive);
+        assert!(defaults.case_sensitive);
+        // -C on the command line beats -B in the file.
+        assert_eq!(3, config.before_context);
+        // Globs add up, with the command line's last.
+        assert_eq!(["!target/**", "*.rs"], config.walker.globs());
+
+        let origin = |name| config.origins.get(name).cloned();
+        let file = Origin::File(path.clone());
+        assert_eq!(Some(Origin::Env("MINIGREP_COLOR".into())), origin("color"));
+        assert_eq!(Some(Origin::CommandLine), origin("before_context"));
+        assert_eq!(Some(Origin::CommandLine), origin("globs"));
+        assert_eq!(Some(file), origin("case_sensitive"));
+        assert_eq!(None, origin("threads"));
+        assert!(config.describe().contains(
+            "color = never                    (environment variable"
+        ));
+    }
+
+    #[test]
+    fn bad_defaults_name_where_they_came_from() {
+        let env = |name: &str| {
+            (name == "MINIGREP_CONTEXT").then(|| "lots".to_string())
+        };
+
+        assert_eq!(
+            "environment variable MINIGREP_CONTEXT: invalid value 'lots' for \
+             '--context': expected a number",
+            parse(args(&["to"]), env).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn context_needs_a_number() {
+        let many = args(&["-A", "many", "to", "poem.txt"]);
+        assert!(parse(many, no_env).is_err());
+        assert!(parse(args(&["to", "poem.txt", "-C"]), no_env).is_err());
+    }
+}
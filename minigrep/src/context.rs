@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::io;
+
+use crate::Match;
+
+/// A line of output once context has been added around the matches.
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+    Match(Match<'a>),
+    Context {
+        line_number: usize,
+        line: &'a str,
+    },
+    /// Two groups of lines aren't adjacent; printed as `--` like grep.
+    Break,
+}
+
+/// Decides, one line at a time, which lines around the matches get output.
+///
+/// Windows that touch or overlap are merged, so no line is output twice,
+/// and a `Line::Break` goes wherever lines were skipped between two groups.
+/// At most `before` lines are ever held back.
+pub(crate) struct Context {
+    before: usize,
+    after: usize,
+    /// The lines just before the current one that haven't been output.
+    buffer: VecDeque<(usize, String)>,
+    after_left: usize,
+    last_output: Option<usize>,
+}
+
+impl Context {
+    pub(crate) fn new(before: usize, after: usize) -> Context {
+        Context {
+            before,
+            after,
+            // Grown as lines come rather than reserved, since `before` can
+            // be any number the user typed.
+            buffer: VecDeque::new(),
+            after_left: 0,
+            last_output: None,
+        }
+    }
+
+    pub(crate) fn matched(
+        &mut self,
+        m: Match<'_>,
+        emit: &mut impl FnMut(Line<'_>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let first = m.line_number - self.buffer.len();
+        let enabled = self.before > 0 || self.after > 0;
+        if enabled && self.last_output.is_some_and(|last| first > last + 1) {
+            emit(Line::Break)?;
+        }
+
+        for (line_number, line) in self.buffer.drain(..) {
+            emit(Line::Context {
+                line_number,
+                line: &line,
+            })?;
+        }
+
+        // A match found by a multiline search may run over several lines.
+        self.last_output = Some(m.line_number + m.line.matches('\n').count());
+        self.after_left = self.after;
+        emit(Line::Match(m))
+    }
+
+    /// Whether lines of context after a match are still to come.
+    pub(crate) fn after_pending(&self) -> bool {
+        self.after_left > 0
+    }
+
+    pub(crate) fn unmatched(
+        &mut self,
+        line_number: usize,
+        line: &str,
+        emit: &mut impl FnMut(Line<'_>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.after_left > 0 {
+            self.after_left -= 1;
+            self.last_output = Some(line_number);
+            return emit(Line::Context { line_number, line });
+        }
+
+        if self.before > 0 {
+            // Reuse the oldest line's allocation once the buffer is full.
+            let mut owned = if self.buffer.len() == self.before {
+                self.buffer.pop_front().map(|(_, s)| s).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            owned.clear();
+            owned.push_str(line);
+            self.buffer.push_back((line_number, owned));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Transparent decompression for `-z`.
+//!
+//! Compressed inputs are recognized by their first few bytes rather than
+//! their names, so a rotated `app.log.1` that happens to be gzipped is
+//! still found, and a `.gz` that isn't compressed at all is searched as it
+//! is.
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// A compression format `-z` can see through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Format {
+    /// The format whose magic bytes `head` starts with, if any.
+    pub(crate) fn detect(head: &[u8]) -> Option<Format> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Format::Gzip)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Format::Xz)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Format::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// A reader of what `reader` decompresses to, decompressing as it
+    /// goes. Any error in the compressed data is reported as corruption,
+    /// so it can't be mistaken for the text inside being invalid UTF-8.
+    pub(crate) fn decoder<'a>(
+        self,
+        reader: impl BufRead + 'a,
+    ) -> io::Result<impl Read + 'a> {
+        let inner: Box<dyn Read + 'a> = match self {
+            // Several gzip members one after another, as `cat a.gz b.gz`
+            // makes, decompress to their contents one after another too.
+            Format::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Format::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+            Format::Zstd => Box::new(ZstdDecoder::with_buffer(reader)?),
+        };
+
+        Ok(Decoder {
+            format: self,
+            inner,
+        })
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Gzip => write!(f, "gzip"),
+            Format::Xz => write!(f, "xz"),
+            Format::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    format: Format,
+    inner: Box<dyn Read + 'a>,
+}
+
+impl Read for Decoder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|e| {
+            io::Error::other(format!("corrupt {} data: {}", self.format, e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use xz2::write::XzEncoder;
+
+    fn compressed(format: Format, text: &str) -> Vec<u8> {
+        match format {
+            Format::Gzip => {
+                let mut encoder =
+                    GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(text.as_bytes()).unwrap();
+                encoder.finish().unwrap()
+            }
+            Format::Xz => {
+                let mut encoder = XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(text.as_bytes()).unwrap();
+                encoder.finish().unwrap()
+            }
+            Format::Zstd => zstd::encode_all(text.as_bytes(), 0).unwrap(),
+        }
+    }
+
+    fn decompressed(bytes: &[u8]) -> io::Result<String> {
+        let format = Format::detect(bytes).unwrap();
+        let mut text = String::new();
+        format.decoder(bytes)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for format in [Format::Gzip, Format::Xz, Format::Zstd] {
+            let bytes = compressed(format, "Are you nobody, too?\n");
+
+            assert_eq!(Some(format), Format::detect(&bytes));
+            assert_eq!("Are you nobody, too?\n", decompressed(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn plain_text_is_not_detected() {
+        assert_eq!(None, Format::detect(b"I'm nobody! Who are you?\n"));
+        assert_eq!(None, Format::detect(b""));
+    }
+
+    #[test]
+    fn concatenated_gzip_members() {
+        let mut bytes = compressed(Format::Gzip, "one\n");
+        bytes.extend(compressed(Format::Gzip, "two\n"));
+
+        assert_eq!("one\ntwo\n", decompressed(&bytes).unwrap());
+    }
+
+    #[test]
+    fn corruption_is_reported_as_such() {
+        for format in [Format::Gzip, Format::Xz, Format::Zstd] {
+            let mut bytes = compressed(format, &"nobody\n".repeat(100));
+            bytes.truncate(bytes.len() / 2);
+
+            let err = decompressed(&bytes).unwrap_err();
+            assert_ne!(io::ErrorKind::InvalidData, err.kind());
+            assert!(
+                err.to_string()
+                    .starts_with(&format!("corrupt {} data: ", format)),
+                "{}",
+                err
+            );
+        }
+    }
+}
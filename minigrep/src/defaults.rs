@@ -0,0 +1,282 @@
+//! Defaults for options, read from the `MINIGREP_CONFIG` file and from
+//! `MINIGREP_*` environment variables before the command line is parsed.
+//!
+//! The file is TOML, with one key per option, named the way its long flag
+//! is:
+//!
+//! ```toml
+//! color = "always"
+//! smart-case = true
+//! context = 2
+//! glob = ["!target/**", "!*.min.js"]
+//! ```
+//!
+//! Each variable is the same name in upper case with `_` for `-`, such as
+//! `MINIGREP_SMART_CASE=true` or `MINIGREP_GLOB='!target/** !*.min.js'`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{takes_value, Origin};
+use crate::Error;
+
+/// The options that can be given defaults: the ones that say how to search
+/// rather than what for, or what to do with the results.
+pub(crate) const OPTIONS: &[&str] = &[
+    "regex",
+    "ignore-case",
+    "case-sensitive",
+    "smart-case",
+    "line-number",
+    "after-context",
+    "before-context",
+    "context",
+    "color",
+    "search-zip",
+    "glob",
+    "type",
+    "hidden",
+    "no-ignore",
+    "threads",
+];
+
+/// A default for one option.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Setting {
+    pub(crate) origin: Origin,
+    /// The option's long name, without `--`.
+    pub(crate) name: String,
+    pub(crate) value: Value,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Value {
+    /// For a flag: `true` turns it on, and `false` back off.
+    Flag(bool),
+    /// For an option that takes a value, each value it's given, in order.
+    Values(Vec<String>),
+}
+
+/// Every default, lowest precedence first: the config file's, then the
+/// environment's. `env` looks up an environment variable.
+pub(crate) fn load(
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<Setting>, Error> {
+    let mut settings = match env("MINIGREP_CONFIG") {
+        Some(path) => from_file(PathBuf::from(path))?,
+        None => Vec::new(),
+    };
+
+    // The variable minigrep has always read; MINIGREP_IGNORE_CASE and the
+    // other MINIGREP_* variables come after it, so they win.
+    if env("CASE_INSENSITIVE").is_some() {
+        settings.push(Setting {
+            origin: Origin::Env("CASE_INSENSITIVE".to_string()),
+            name: "ignore-case".to_string(),
+            value: Value::Flag(true),
+        });
+    }
+
+    for name in OPTIONS {
+        let var = format!("MINIGREP_{}", name.to_uppercase().replace('-', "_"));
+        if let Some(value) = env(&var) {
+            let origin = Origin::Env(var);
+            let value = if takes_value(name) {
+                Value::Values(if matches!(*name, "glob" | "type") {
+                    value.split_whitespace().map(String::from).collect()
+                } else {
+                    vec![value]
+                })
+            } else {
+                Value::Flag(boolean(&origin, name, &value)?)
+            };
+
+            settings.push(Setting {
+                origin,
+                name: name.to_string(),
+                value,
+            });
+        }
+    }
+
+    Ok(settings)
+}
+
+fn from_file(path: PathBuf) -> Result<Vec<Setting>, Error> {
+    let contents =
+        fs::read_to_string(&path).map_err(|e| Error::reading(&path, e))?;
+    let table: toml::Table =
+        toml::from_str(&contents).map_err(|source| Error::InvalidConfig {
+            path: path.clone(),
+            source,
+        })?;
+    let origin = Origin::File(path);
+
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let name = key.replace('_', "-");
+            let invalid = |expected| Error::Setting {
+                origin: origin.clone(),
+                source: Box::new(Error::InvalidValue {
+                    option: key.clone(),
+                    value: value.to_string(),
+                    expected,
+                }),
+            };
+
+            if !OPTIONS.contains(&name.as_str()) {
+                return Err(Error::Setting {
+                    origin: origin.clone(),
+                    source: Box::new(Error::UnknownOption(key.clone())),
+                });
+            }
+
+            let value = match (&value, takes_value(&name)) {
+                (toml::Value::Boolean(on), false) => Value::Flag(*on),
+                (_, false) => return Err(invalid("true or false")),
+                (toml::Value::Array(items), true) => Value::Values(
+                    items
+                        .iter()
+                        .map(scalar)
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid("a list of strings"))?,
+                ),
+                (value, true) => Value::Values(vec![scalar(value)
+                    .ok_or_else(|| invalid("a string or number"))?]),
+            };
+
+            Ok(Setting {
+                origin: origin.clone(),
+                name,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// A string or number from the config file as an option's value.
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// An environment variable's value for a flag: `1`, `true` or `yes` to
+/// turn it on, and `0`, `false`, `no` or nothing at all to turn it off.
+fn boolean(origin: &Origin, name: &str, value: &str) -> Result<bool, Error> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(true),
+        "" | "0" | "false" | "no" => Ok(false),
+        _ => Err(Error::Setting {
+            origin: origin.clone(),
+            source: Box::new(Error::InvalidValue {
+                option: name.to_string(),
+                value: value.to_string(),
+                expected: "true or false",
+            }),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+    use std::collections::HashMap;
+
+    fn load_from(vars: &[(&str, &str)]) -> Result<Vec<Setting>, Error> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        load(&|name| vars.get(name).cloned())
+    }
+
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = temp_path(&format!("{}.toml", name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn file_then_environment() {
+        let path = config_file(
+            "defaults",
+            "color = \"always\"\nsmart_case = true\ncontext = 2\n\
+             glob = [\"!target/**\"]\n",
+        );
+        let settings = load_from(&[
+            ("MINIGREP_CONFIG", path.to_str().unwrap()),
+            ("MINIGREP_LINE_NUMBER", "yes"),
+            ("MINIGREP_GLOB", "*.rs  *.toml"),
+        ]);
+        fs::remove_file(&path).unwrap();
+        let file = Origin::File(path);
+        let env = |var: &str| Origin::Env(var.to_string());
+        let setting = |origin: Origin, name: &str, value| Setting {
+            origin,
+            name: name.to_string(),
+            value,
+        };
+        let values = |values: &[&str]| {
+            Value::Values(values.iter().map(|s| s.to_string()).collect())
+        };
+
+        assert_eq!(
+            vec![
+                setting(file.clone(), "color", values(&["always"])),
+                setting(file.clone(), "context", values(&["2"])),
+                setting(file.clone(), "glob", values(&["!target/**"])),
+                setting(file, "smart-case", Value::Flag(true)),
+                setting(
+                    env("MINIGREP_LINE_NUMBER"),
+                    "line-number",
+                    Value::Flag(true)
+                ),
+                setting(
+                    env("MINIGREP_GLOB"),
+                    "glob",
+                    values(&["*.rs", "*.toml"])
+                ),
+            ],
+            settings.unwrap()
+        );
+    }
+
+    #[test]
+    fn nothing_set() {
+        assert_eq!(Vec::<Setting>::new(), load_from(&[]).unwrap());
+    }
+
+    #[test]
+    fn bad_settings() {
+        assert!(matches!(
+            load_from(&[("MINIGREP_HIDDEN", "sometimes")]),
+            Err(Error::Setting { .. })
+        ));
+
+        for (name, contents) in [
+            ("syntax", "color = "),
+            ("unknown", "replace = \"x\"\n"),
+            ("flag", "hidden = \"yes\"\n"),
+            ("list", "glob = [true]\n"),
+        ] {
+            let path = config_file(name, contents);
+            let result =
+                load_from(&[("MINIGREP_CONFIG", path.to_str().unwrap())]);
+            fs::remove_file(&path).unwrap();
+
+            assert!(
+                matches!(
+                    result,
+                    Err(Error::InvalidConfig { .. } | Error::Setting { .. })
+                ),
+                "{}",
+                contents
+            );
+        }
+    }
+}
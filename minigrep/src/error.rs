@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::Origin;
+
+/// Everything that can go wrong in minigrep, from parsing the command line
+/// to reading a file.
+#[derive(Debug)]
+pub enum Error {
+    /// No QUERY was given.
+    MissingQuery,
+    /// An option minigrep doesn't know, as it was typed.
+    UnknownOption(String),
+    /// An option that needs a value came last, without one.
+    MissingValue(String),
+    /// A flag was given a value with `--flag=value`.
+    UnexpectedValue(String),
+    /// An option's value couldn't be used.
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// The first option only makes sense along with the second.
+    NeedsOption(&'static str, &'static str),
+    /// Two options that can't be used together.
+    Conflict(&'static str, &'static str),
+    /// `-E` was given and QUERY isn't a valid regular expression.
+    InvalidPattern(regex::Error),
+    /// A `--glob` isn't a valid glob, or a `--type` isn't a known file type.
+    InvalidFilter(ignore::Error),
+    /// The `MINIGREP_CONFIG` file isn't valid TOML.
+    InvalidConfig {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// A default from the config file or the environment couldn't be used.
+    Setting { origin: Origin, source: Box<Error> },
+    /// `Config::new` was asked to parse `--help` or `--version`, which don't
+    /// describe a search; use `Command::parse` to handle them.
+    NotASearch,
+    /// A file or directory couldn't be opened or read.
+    Io { path: PathBuf, source: io::Error },
+    /// A file isn't valid UTF-8, so its lines can't be searched as text.
+    InvalidUtf8 { path: PathBuf },
+    /// Results couldn't be written to standard output.
+    Output(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingQuery => write!(f, "Didn't get a query string"),
+            Error::UnknownOption(option) => {
+                write!(f, "unknown option '{}'", option)
+            }
+            Error::MissingValue(option) => {
+                write!(f, "option '{}' needs a value", option)
+            }
+            Error::UnexpectedValue(option) => {
+                write!(f, "option '{}' doesn't take a value", option)
+            }
+            Error::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value '{}' for '{}': expected {}",
+                value, option, expected
+            ),
+            Error::NeedsOption(option, needs) => {
+                write!(f, "option '{}' needs '{}'", option, needs)
+            }
+            Error::Conflict(a, b) => {
+                write!(f, "options '{}' and '{}' can't be used together", a, b)
+            }
+            Error::InvalidPattern(e) => write!(f, "{}", e),
+            Error::InvalidFilter(e) => write!(f, "{}", e),
+            Error::InvalidConfig { path, source } => {
+                write!(f, "{}: {}", path.display(), source)
+            }
+            Error::Setting { origin, source } => {
+                write!(f, "{}: {}", origin, source)
+            }
+            Error::NotASearch => {
+                write!(f, "--help and --version don't describe a search")
+            }
+            Error::Io { path, source } => {
+                write!(f, "{}: {}", path.display(), source)
+            }
+            Error::InvalidUtf8 { path } => {
+                write!(f, "{}: not valid UTF-8", path.display())
+            }
+            Error::Output(e) => write!(f, "can't write output: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidPattern(e) => Some(e),
+            Error::InvalidFilter(e) => Some(e),
+            Error::InvalidConfig { source, .. } => Some(source),
+            Error::Setting { source, .. } => Some(source.as_ref()),
+            Error::Io { source, .. } => Some(source),
+            Error::Output(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// An error from reading `path`, telling invalid UTF-8 apart from
+    /// other I/O failures.
+    pub(crate) fn reading(
+        path: impl Into<PathBuf>,
+        source: io::Error,
+    ) -> Error {
+        let path = path.into();
+
+        if source.kind() == io::ErrorKind::InvalidData {
+            Error::InvalidUtf8 { path }
+        } else {
+            Error::Io { path, source }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! `--interactive`: a terminal UI that searches one file as the query is
+//! typed.
+//!
+//! Everything the keys do happens in [`App`], which knows nothing about the
+//! terminal, so a test can drive it with scripted key events. [`run`] only
+//! sets the terminal up and passes it real ones.
+
+use std::fs;
+use std::io::{self, Write};
+
+use crossterm::event::KeyModifiers;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+
+use crate::config::compile_regex;
+use crate::searcher::Searcher;
+use crate::{matcher, selected_lines, Config, Error};
+
+/// What a key did.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    /// Enter was pressed on a result: its line number and text.
+    Choose(usize, String),
+    /// The user gave up without choosing anything.
+    Quit,
+}
+
+/// The state of the UI: the query so far and what it finds.
+pub struct App<'a> {
+    contents: &'a str,
+    /// The rest of the command line, which decides how the query matches
+    /// just as it would outside the UI.
+    config: &'a Config,
+    query: String,
+    case_sensitive: bool,
+    /// Whether the query isn't a valid regular expression, with `-E`.
+    invalid: bool,
+    /// The matching lines, with their line numbers.
+    results: Vec<(usize, &'a str)>,
+    selected: usize,
+    /// The first result shown, once there are more than fit.
+    scroll: usize,
+}
+
+impl<'a> App<'a> {
+    /// Search `contents` the way `config` says, starting with its first
+    /// pattern as the query.
+    pub fn new(contents: &'a str, config: &'a Config) -> App<'a> {
+        let query = config.patterns.first().map_or("", String::as_str);
+        let mut app = App {
+            contents,
+            config,
+            query: query.to_string(),
+            case_sensitive: config.case_sensitive,
+            invalid: false,
+            results: Vec::new(),
+            selected: 0,
+            scroll: 0,
+        };
+        app.refresh();
+        app
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub fn results(&self) -> &[(usize, &'a str)] {
+        &self.results
+    }
+
+    /// The result Enter would choose, if there are any.
+    pub fn selected(&self) -> Option<(usize, &'a str)> {
+        self.results.get(self.selected).copied()
+    }
+
+    /// Handle one key press.
+    ///
+    /// Typing edits the query, Up and Down move the selection, Ctrl-T
+    /// toggles case sensitivity, Enter chooses the selected line, and Esc
+    /// or Ctrl-C quits.
+    pub fn handle(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Char('t') if ctrl => {
+                self.case_sensitive = !self.case_sensitive;
+                self.refresh();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refresh();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refresh();
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.results.len() => {
+                self.selected += 1
+            }
+            KeyCode::Enter => {
+                if let Some((line_number, line)) = self.selected() {
+                    return Action::Choose(line_number, line.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    /// Search again after the query or case sensitivity changed, with
+    /// the same kernel as the command line, so `-w`, `-x`, `-E` and `-v`
+    /// select the same lines here as there.
+    fn refresh(&mut self) {
+        self.selected = 0;
+        self.scroll = 0;
+        self.invalid = false;
+        self.results = Vec::new();
+        if self.query.is_empty() {
+            return;
+        }
+
+        let patterns = [self.query.clone()];
+        let regex = match &self.config.regex {
+            Some(_) => match compile_regex(
+                &patterns,
+                self.case_sensitive,
+                self.config.line_regexp,
+                false,
+            ) {
+                Ok(regex) => Some(regex),
+                Err(_) => {
+                    self.invalid = true;
+                    return;
+                }
+            },
+            None => None,
+        };
+        let matcher = matcher::for_patterns(
+            self.config,
+            &patterns,
+            self.case_sensitive,
+            regex.as_ref(),
+        );
+        let searcher =
+            Searcher::new(matcher).invert_match(self.config.invert_match);
+
+        self.results = selected_lines(&searcher, self.contents);
+    }
+
+    /// Draw the UI in a `width` by `height` terminal: the query, a status
+    /// line, then as many results as fit, scrolled to keep the selected one
+    /// in view.
+    pub fn render(
+        &mut self,
+        out: &mut impl Write,
+        width: u16,
+        height: u16,
+    ) -> io::Result<()> {
+        let width = usize::from(width);
+        let rows = usize::from(height).saturating_sub(2);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if rows > 0 && self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+
+        queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 1))?;
+        let case = if self.case_sensitive {
+            "case-sensitive"
+        } else {
+            "ignoring case"
+        };
+        let found = if self.invalid {
+            "invalid pattern".to_string()
+        } else {
+            format!("{} matches", self.results.len())
+        };
+        let status = format!(
+            "{}, {} (Ctrl-T toggles); Up/Down select, Enter prints, Esc quits",
+            found, case
+        );
+        queue!(out, Print(truncate(&status, width)))?;
+
+        let shown = self.results.iter().enumerate().skip(self.scroll);
+        for (row, (i, (line_number, line))) in shown.take(rows).enumerate() {
+            let text = format!("{:>6}: {}", line_number, line);
+            queue!(out, cursor::MoveTo(0, row as u16 + 2))?;
+            if i == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(truncate(&text, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(truncate(&text, width)))?;
+            }
+        }
+
+        let prompt = format!("> {}", self.query);
+        let column = prompt.chars().count().min(width) as u16;
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            Print(truncate(&prompt, width)),
+            cursor::MoveTo(column, 0)
+        )?;
+        out.flush()
+    }
+}
+
+/// Drive `app` with `keys`, drawing it on `out` after each one, until a
+/// line is chosen or the user quits. Key releases are ignored, and any
+/// other event just redraws it. Running out of keys counts as quitting.
+pub fn drive(
+    app: &mut App<'_>,
+    keys: impl IntoIterator<Item = io::Result<Event>>,
+    out: &mut impl Write,
+    size: impl Fn() -> io::Result<(u16, u16)>,
+) -> io::Result<Option<(usize, String)>> {
+    let (width, height) = size()?;
+    app.render(out, width, height)?;
+
+    for event in keys {
+        let key = match event? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Key(_) => continue,
+            // Such as the terminal being resized.
+            _ => {
+                let (width, height) = size()?;
+                app.render(out, width, height)?;
+                continue;
+            }
+        };
+
+        match app.handle(key) {
+            Action::Continue => {}
+            Action::Choose(line_number, line) => {
+                return Ok(Some((line_number, line)))
+            }
+            Action::Quit => return Ok(None),
+        }
+
+        let (width, height) = size()?;
+        app.render(out, width, height)?;
+    }
+
+    Ok(None)
+}
+
+/// Run the UI on the terminal for the one file in `config.paths`, drawing
+/// on stderr so that stdout only gets the chosen line, and return it.
+pub fn run(config: &Config) -> Result<Option<(usize, String)>, Error> {
+    let path = &config.paths[0];
+    let contents =
+        fs::read_to_string(path).map_err(|e| Error::reading(path, e))?;
+    let mut app = App::new(&contents, config);
+
+    let mut out = io::stderr();
+    let _terminal = Terminal::enter(&mut out).map_err(Error::Output)?;
+    let keys = std::iter::from_fn(|| Some(event::read()));
+
+    drive(&mut app, keys, &mut out, terminal::size).map_err(Error::Output)
+}
+
+/// Puts the terminal in raw mode on an alternate screen, and back the way
+/// it was when dropped, even if drawing fails part way.
+struct Terminal;
+
+impl Terminal {
+    fn enter(out: &mut impl Write) -> io::Result<Terminal> {
+        terminal::enable_raw_mode()?;
+        execute!(out, terminal::EnterAlternateScreen)?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// At most the first `width` characters of `text`.
+fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.";
+
+    /// An `--interactive` search of `poem.txt` with `args` too.
+    fn config(args: &[&str]) -> Config {
+        let mut all = vec!["--interactive"];
+        all.extend(args);
+        all.push("poem.txt");
+        crate::testing::config(&all)
+    }
+
+    fn key(code: KeyCode) -> io::Result<Event> {
+        Ok(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+    }
+
+    fn ctrl(c: char) -> io::Result<Event> {
+        Ok(Event::Key(KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::CONTROL,
+        )))
+    }
+
+    fn typed(text: &str) -> Vec<io::Result<Event>> {
+        text.chars().map(|c| key(KeyCode::Char(c))).collect()
+    }
+
+    fn drive_keys(
+        app: &mut App<'_>,
+        keys: Vec<io::Result<Event>>,
+    ) -> Option<(usize, String)> {
+        let mut screen = Vec::new();
+        drive(app, keys, &mut screen, || Ok((80, 24))).unwrap()
+    }
+
+    #[test]
+    fn typing_refines_the_results() {
+        let config = config(&[]);
+        let mut app = App::new(POEM, &config);
+        assert!(app.results().is_empty());
+
+        app.handle(KeyEvent::from(KeyCode::Char('u')));
+        assert_eq!(4, app.results().len());
+        for c in "s ".chars() {
+            app.handle(KeyEvent::from(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            vec![(3, "Then there's a pair of us - don't tell!")],
+            app.results()
+        );
+
+        app.handle(KeyEvent::from(KeyCode::Backspace));
+        assert_eq!("us", app.query());
+        assert_eq!(2, app.results().len());
+    }
+
+    #[test]
+    fn results_carry_their_own_line_numbers() {
+        let contents = "apple\nbanana\napple\ncherry\n\nApple pie\napple";
+        let config = config(&["-e", "apple"]);
+        let mut app = App::new(contents, &config);
+
+        assert_eq!(
+            vec![(1, "apple"), (3, "apple"), (7, "apple")],
+            app.results()
+        );
+
+        app.handle(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL));
+        assert_eq!(
+            vec![(1, "apple"), (3, "apple"), (6, "Apple pie"), (7, "apple")],
+            app.results()
+        );
+    }
+
+    #[test]
+    fn options_select_the_same_lines_as_the_command_line() {
+        let contents = "to be\ntoo late\nTo\nstop";
+
+        let words = config(&["-w", "-e", "to"]);
+        let app = App::new(contents, &words);
+        assert_eq!(vec![(1, "to be")], app.results());
+
+        let lines = config(&["-x", "-i", "-e", "to"]);
+        let app = App::new(contents, &lines);
+        assert_eq!(vec![(3, "To")], app.results());
+
+        let inverted = config(&["-v", "-e", "to"]);
+        let app = App::new(contents, &inverted);
+        assert_eq!(vec![(3, "To")], app.results());
+
+        let regex = config(&["-E", "-e", "^to+ "]);
+        let mut app = App::new(contents, &regex);
+        assert_eq!(vec![(1, "to be"), (2, "too late")], app.results());
+        app.handle(KeyEvent::from(KeyCode::Char('(')));
+        assert!(app.results().is_empty());
+        let mut screen = Vec::new();
+        app.render(&mut screen, 80, 24).unwrap();
+        assert!(String::from_utf8_lossy(&screen).contains("invalid pattern"));
+    }
+
+    #[test]
+    fn arrows_select_and_enter_prints() {
+        let config = config(&[]);
+        let mut app = App::new(POEM, &config);
+        let mut keys = typed("you");
+        keys.extend([
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Up),
+            key(KeyCode::Enter),
+        ]);
+
+        assert_eq!(
+            Some((2, "Are you nobody, too?".to_string())),
+            drive_keys(&mut app, keys)
+        );
+    }
+
+    #[test]
+    fn ctrl_t_toggles_case() {
+        let config = config(&[]);
+        let mut app = App::new(POEM, &config);
+        let mut keys = typed("they");
+        keys.extend([ctrl('t'), key(KeyCode::Enter)]);
+
+        assert_eq!(
+            Some((4, "They'd banish us, you know.".to_string())),
+            drive_keys(&mut app, keys)
+        );
+        assert!(!app.case_sensitive());
+    }
+
+    #[test]
+    fn escape_and_running_out_of_keys_quit() {
+        let config = config(&["-e", "nobody"]);
+        let mut app = App::new(POEM, &config);
+
+        assert_eq!(None, drive_keys(&mut app, vec![key(KeyCode::Esc)]));
+        assert_eq!(None, drive_keys(&mut app, typed("!")));
+        assert_eq!(None, drive_keys(&mut app, vec![ctrl('c')]));
+    }
+
+    #[test]
+    fn enter_without_results_does_nothing() {
+        let config = config(&["-e", "frog"]);
+        let mut app = App::new(POEM, &config);
+
+        assert_eq!(
+            Action::Continue,
+            app.handle(KeyEvent::from(KeyCode::Enter))
+        );
+    }
+
+    #[test]
+    fn rendering_scrolls_to_the_selection() {
+        let config = config(&["-e", "o"]);
+        let mut app = App::new(POEM, &config);
+        for _ in 0..3 {
+            app.handle(KeyEvent::from(KeyCode::Down));
+        }
+        let mut screen = Vec::new();
+        app.render(&mut screen, 40, 4).unwrap();
+        let screen = String::from_utf8_lossy(&screen);
+
+        assert!(screen.contains("> o"));
+        assert!(screen.contains("4 matches"));
+        assert!(screen.contains("     4: They'd banish us, you know."));
+        assert!(!screen.contains("     1: "));
+    }
+}
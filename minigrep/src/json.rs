@@ -0,0 +1,108 @@
+//! JSON Lines output for `--json`, modelled on `rg --json`.
+//!
+//! Every record is one line holding `{"type": ..., "data": {...}}`. Each
+//! input gets a `begin` record, then a `match` or `context` record per line,
+//! then an `end` record with that input's statistics. A single `summary`
+//! record with the totals comes last.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::printer::Stats;
+use crate::Line;
+
+pub(crate) fn begin(out: &mut impl Write, path: &str) -> io::Result<()> {
+    record(out, "begin", json!({ "path": path }))
+}
+
+pub(crate) fn line(
+    out: &mut impl Write,
+    path: &str,
+    line: &Line<'_>,
+) -> io::Result<()> {
+    match line {
+        Line::Match(m) => {
+            let submatches: Vec<Value> = m
+                .spans
+                .iter()
+                .map(|span| {
+                    json!({
+                        "text": &m.line[span.clone()],
+                        "start": span.start,
+                        "end": span.end,
+                    })
+                })
+                .collect();
+
+            record(
+                out,
+                "match",
+                json!({
+                    "path": path,
+                    "line_number": m.line_number,
+                    // 1-based, in bytes, like `grep --column`; absent when
+                    // the line was selected by -v and nothing matched.
+                    "column": m.spans.first().map(|span| span.start + 1),
+                    "absolute_offset": m.byte_offset,
+                    "line": m.line,
+                    "submatches": submatches,
+                }),
+            )
+        }
+        Line::Context { line_number, line } => record(
+            out,
+            "context",
+            json!({
+                "path": path,
+                "line_number": line_number,
+                "line": line,
+            }),
+        ),
+        // Records already carry line numbers, so gaps need no marker.
+        Line::Break => Ok(()),
+    }
+}
+
+pub(crate) fn end(
+    out: &mut impl Write,
+    path: &str,
+    stats: &Stats,
+    elapsed: Duration,
+) -> io::Result<()> {
+    record(
+        out,
+        "end",
+        json!({
+            "path": path,
+            "stats": stats_value(stats, elapsed),
+        }),
+    )
+}
+
+pub(crate) fn summary(
+    out: &mut impl Write,
+    stats: &Stats,
+    elapsed: Duration,
+) -> io::Result<()> {
+    record(
+        out,
+        "summary",
+        json!({ "stats": stats_value(stats, elapsed) }),
+    )
+}
+
+fn stats_value(stats: &Stats, elapsed: Duration) -> Value {
+    json!({
+        "searches": stats.searches,
+        "searches_with_match": stats.searches_with_match,
+        "matched_lines": stats.matched_lines,
+        "matches": stats.matches,
+        "elapsed_secs": elapsed.as_secs_f64(),
+    })
+}
+
+fn record(out: &mut impl Write, kind: &str, data: Value) -> io::Result<()> {
+    writeln!(out, "{}", json!({ "type": kind, "data": data }))
+}
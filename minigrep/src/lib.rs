@@ -1,71 +1,476 @@
-use std::fs;
-use std::env;
-use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-pub struct Config {
-    pub query: String,
-    pub file_path: String,
-    pub case_sensitive: bool,
+use memmap2::Mmap;
+use regex::Regex;
+
+use decompress::Format;
+use printer::{Printer, Stats};
+
+pub mod config;
+pub mod context;
+mod decompress;
+mod defaults;
+mod error;
+pub mod interactive;
+mod json;
+pub mod matcher;
+pub mod parallel;
+mod printer;
+mod replace;
+pub mod searcher;
+pub mod stream;
+#[cfg(test)]
+mod testing;
+pub mod walk;
+
+pub use config::{ColorChoice, Command, Config, Origin, USAGE};
+pub use context::Line;
+pub use error::Error;
+pub use matcher::Matcher;
+pub use searcher::{Searcher, Sink};
+pub use stream::{search_reader, search_text};
+
+/// A selected line, along with where it was found.
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    /// The line's number, counting from 1.
+    pub line_number: usize,
+    /// Offset in bytes of the start of the line within the searched text.
+    pub byte_offset: usize,
+    pub line: &'a str,
+    /// Byte ranges of each match within `line`, for highlighting. Empty
+    /// for lines selected by `-v`, since nothing in them matched.
+    pub spans: Vec<Range<usize>>,
 }
 
-impl Config {
-    pub fn new(
-        mut args: impl Iterator<Item = String>
-    ) -> Result<Config, &'static str> {
-        args.next();
+/// How a run went. Problems with individual files are reported on stderr
+/// as they happen and counted here rather than ending the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// Whether any line was selected.
+    pub matched: bool,
+    /// How many inputs couldn't be searched.
+    pub errors: usize,
+}
+
+impl Outcome {
+    /// grep's exit status: 0 if a line was selected, 1 if none was, and 2
+    /// if anything went wrong, even if there were matches too.
+    pub fn exit_code(&self) -> i32 {
+        if self.errors > 0 {
+            2
+        } else if self.matched {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+pub fn run(config: Config) -> Result<Outcome, Error> {
+    if config.interactive {
+        return run_interactive(&config);
+    }
+
+    let start = Instant::now();
+    let inputs = inputs(&config);
+    let show_names = inputs.len() > 1
+        || config.paths.iter().any(|path| Path::new(path).is_dir());
+    let context = config.before_context > 0 || config.after_context > 0;
+    let color = match config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal(),
+    };
+    let printer = Printer::new(&config, show_names, color);
+    let mut failures = 0;
+    let mut totals = Stats::default();
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
+    let mut report =
+        |input: &Input, result: io::Result<Option<Stats>>| match result {
+            Ok(Some(stats)) => {
+                totals += stats;
+                Ok(())
+            }
+            Ok(None) => {
+                eprintln!("minigrep: {}: binary file, skipping", input.name());
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Err(e),
+            Err(e) => {
+                eprintln!("minigrep: {}", Error::reading(input.name(), e));
+                failures += 1;
+                Ok(())
+            }
         };
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    // A single input is streamed straight to stdout, so output shows up as
+    // soon as it's found, even from a pipe that never closes.
+    let outcome = if let [input] = &inputs[..] {
+        let result = search_input(&config, &printer, input, &mut out);
+        report(input, result)
+    } else {
+        let mut printed_any = false;
+
+        parallel::ordered_map(
+            &inputs,
+            config.threads,
+            |input| {
+                let mut output = Vec::new();
+                let result =
+                    search_input(&config, &printer, input, &mut output);
+                (output, result)
+            },
+            |input, (output, result)| {
+                if !output.is_empty() {
+                    // Separate one file's context groups from the last's.
+                    if context && printed_any && !config.json {
+                        printer.file_break(&mut out)?;
+                    }
+                    out.write_all(&output)?;
+                    printed_any = true;
+                }
+                report(input, result)
+            },
+        )
+    };
+
+    let outcome = outcome
+        .and_then(|()| printer.summary(&mut out, &totals, start.elapsed()));
+
+    let result = Outcome {
+        matched: totals.matched_lines > 0,
+        errors: failures,
+    };
+
+    match outcome {
+        // Whoever was reading our output has gone away, so just stop.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(result),
+        Err(e) => Err(Error::Output(e)),
+        Ok(()) => Ok(result),
+    }
+}
+
+/// Run the `--interactive` UI and print the line chosen in it, if any,
+/// with its number.
+fn run_interactive(config: &Config) -> Result<Outcome, Error> {
+    let chosen = interactive::run(config)?;
+
+    if let Some((line_number, line)) = &chosen {
+        println!("{}:{}", line_number, line);
+    }
+
+    Ok(Outcome {
+        matched: chosen.is_some(),
+        errors: 0,
+    })
+}
+
+/// One thing to search: standard input, or a file that was either named on
+/// the command line or found by walking a directory.
+struct Input {
+    path: Option<PathBuf>,
+    walked: bool,
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "(standard input)".to_string(),
+        }
+    }
+}
+
+/// Everything `config.paths` names, with directories expanded in place so
+/// the order matches the command line.
+fn inputs(config: &Config) -> Vec<Input> {
+    let mut inputs = Vec::new();
+
+    for path in &config.paths {
+        if path == "-" {
+            inputs.push(Input {
+                path: None,
+                walked: false,
+            });
+        } else if Path::new(path).is_dir() {
+            inputs.extend(
+                config
+                    .walker
+                    .files(Path::new(path))
+                    .into_iter()
+                    .map(|path| Input {
+                        path: Some(path),
+                        walked: true,
+                    }),
+            );
+        } else {
+            inputs.push(Input {
+                path: Some(PathBuf::from(path)),
+                walked: false,
+            });
+        }
+    }
+
+    inputs
+}
+
+/// Search one input and print what it selects to `out`. Returns `None` if
+/// the input was skipped because it looks binary.
+fn search_input(
+    config: &Config,
+    printer: &Printer,
+    input: &Input,
+    out: &mut impl Write,
+) -> io::Result<Option<Stats>> {
+    let name = input.name();
+
+    if config.write || config.dry_run {
+        return edit_input(config, input, out);
+    }
+
+    let path = match &input.path {
+        Some(path) => path,
+        None => {
+            let mut stdin = io::stdin().lock();
+            if config.search_zip {
+                if let Some(format) = Format::detect(stdin.fill_buf()?) {
+                    let decoded = BufReader::new(format.decoder(stdin)?);
+                    return printer.print_reader(&name, decoded, out).map(Some);
+                }
+            }
+            return printer.print_reader(&name, stdin, out).map(Some);
+        }
+    };
+
+    let mut reader =
+        BufReader::with_capacity(BINARY_CHECK_LEN, File::open(path)?);
+
+    if config.search_zip {
+        if let Some(format) = Format::detect(reader.fill_buf()?) {
+            let decoded = BufReader::new(format.decoder(reader)?);
+            return search_buffered(printer, input, decoded, out);
+        }
+    }
+
+    let file = reader.get_ref();
+    if file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: if another process truncates the file while it's being
+        // searched, reading the missing pages kills us with SIGBUS. Like
+        // grep and ripgrep, we accept that for the speed of not copying.
+        let map = unsafe { Mmap::map(file)? };
+        let head = &map[..map.len().min(BINARY_CHECK_LEN)];
+        if input.walked && memchr::memchr(0, head).is_some() {
+            return Ok(None);
+        }
+
+        // Text that isn't UTF-8 is read a line at a time like a smaller
+        // file, so the lines before the bad one are still printed and the
+        // error is the same.
+        return match std::str::from_utf8(&map) {
+            Ok(text) => printer.print_text(&name, text, out).map(Some),
+            Err(_) => printer.print_reader(&name, &map[..], out).map(Some),
         };
+    }
 
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+    search_buffered(printer, input, reader, out)
+}
 
-        Ok(Config {
-            query,
-            file_path,
-            case_sensitive,
-        })
+/// Search one input through `reader`, skipping it if it was found by
+/// walking a directory and looks binary.
+fn search_buffered(
+    printer: &Printer,
+    input: &Input,
+    mut reader: impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<Option<Stats>> {
+    if input.walked && looks_binary(&mut reader)? {
+        return Ok(None);
     }
+
+    printer.print_reader(&input.name(), reader, out).map(Some)
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// Files at least this big are memory-mapped and searched all at once;
+/// smaller ones are cheaper to read through a buffer than to map.
+const MMAP_THRESHOLD: u64 = 1 << 20;
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
+/// How much of the start of a file is checked for a NUL byte to decide
+/// whether it's binary.
+const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// Make the `--replace` edits to one input, or with `--dry-run` print them
+/// as a diff. Returns `None` if the input was skipped because it looks
+/// binary.
+fn edit_input(
+    config: &Config,
+    input: &Input,
+    out: &mut impl Write,
+) -> io::Result<Option<Stats>> {
+    let contents = match &input.path {
+        Some(path) => fs::read_to_string(path)?,
+        None if config.dry_run => {
+            let mut contents = String::new();
+            io::stdin().lock().read_to_string(&mut contents)?;
+            contents
+        }
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "can't be edited in place",
+            ))
+        }
     };
+    if input.walked && contents.contains('\0') {
+        return Ok(None);
+    }
 
-    for line in results {
-        println!("{}", line);
+    let (matches, edits) = replace::edits(config, &contents);
+
+    match &input.path {
+        Some(path) if !config.dry_run && !edits.is_empty() => {
+            replace::write_atomically(path, &replace::apply(&contents, &edits))?
+        }
+        _ => replace::diff(out, &input.name(), &contents, &edits)?,
     }
 
-    Ok(())
+    Ok(Some(Stats {
+        searches: 1,
+        searches_with_match: usize::from(!matches.is_empty()),
+        matched_lines: matches.len(),
+        matches: matches.iter().map(|m| m.spans.len()).sum(),
+    }))
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+/// The lines of `contents` that `config` selects, in order, up to
+/// `config.max_count` of them. Matches are found within each line, even
+/// with `config.multiline`.
+pub fn find_matches<'a>(config: &Config, contents: &'a str) -> Vec<Match<'a>> {
+    let searcher = Searcher::from_config(config);
+
+    matching_lines(contents, |_| true)
+        .filter_map(|m| {
+            let spans = searcher.select(m.line)?;
+            Some(Match { spans, ..m })
+        })
+        .take(config.max_count.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Whether a file found while walking a directory looks binary: a NUL byte
+/// in its first block, the same heuristic grep uses. Only peeks, so the
+/// reader still starts at the beginning of the file.
+fn looks_binary(reader: &mut impl BufRead) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.contains(&0))
+}
+
+/// `line` without its trailing `\n` or `\r\n`, as `str::lines` would give it.
+pub(crate) fn trim_newline(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// The lines of `contents` that `is_match` accepts.
+pub(crate) fn matching_lines(
+    contents: &str,
+    is_match: impl Fn(&str) -> bool,
+) -> impl Iterator<Item = Match<'_>> {
+    let mut byte_offset = 0;
+
     contents
-        .lines()
-        .filter(|line| line.contains(query))
+        .split_inclusive('\n')
+        .enumerate()
+        .map(move |(index, raw)| {
+            let m = Match {
+                line_number: index + 1,
+                byte_offset,
+                line: trim_newline(raw),
+                spans: Vec::new(),
+            };
+            byte_offset += raw.len();
+            m
+        })
+        .filter(move |m| is_match(m.line))
+}
+
+/// The lines of `contents` that `searcher` selects, with their numbers,
+/// found by the same kernel that searches files.
+pub(crate) fn selected_lines<'a, M: Matcher>(
+    searcher: &Searcher<M>,
+    contents: &'a str,
+) -> Vec<(usize, &'a str)> {
+    let mut lines = Vec::new();
+    let mut sink = |line: Line<'_>| {
+        if let Line::Match(m) = line {
+            let end = m.byte_offset + m.line.len();
+            lines.push((m.line_number, &contents[m.byte_offset..end]));
+        }
+        Ok(())
+    };
+    // Neither the text nor the sink can fail.
+    let _ = searcher.search_text(contents, &mut sink);
+
+    lines
+}
+
+/// The lines of `contents` that contain `query`.
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let searcher = Searcher::new(matcher::Literal::new(query));
+
+    selected_lines(&searcher, contents)
+        .into_iter()
+        .map(|(_, line)| line)
         .collect()
 }
 
+/// The lines of `contents` that contain `query`, ignoring case.
 pub fn search_case_insensitive<'a>(
     query: &str,
     contents: &'a str,
 ) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+    let searcher = Searcher::new(matcher::CaseInsensitive::new(query));
 
-    contents
-        .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
+    selected_lines(&searcher, contents)
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// The lines of `contents` with a part within `max_distance` edits of
+/// `query`, closest first.
+pub fn search_fuzzy<'a>(
+    query: &str,
+    max_distance: usize,
+    contents: &'a str,
+) -> Vec<&'a str> {
+    let matcher = matcher::Fuzzy::new(&[query.to_string()], max_distance, true);
+    let searcher = Searcher::new(matcher);
+    let mut lines: Vec<(usize, &str)> = selected_lines(&searcher, contents)
+        .into_iter()
+        .filter_map(|(_, line)| {
+            Some((searcher.matcher().distance(line)?, line))
+        })
+        .collect();
+
+    // A stable sort, so lines the same distance away stay in order.
+    lines.sort_by_key(|&(distance, _)| distance);
+    lines.into_iter().map(|(_, line)| line).collect()
+}
+
+/// The lines of `contents` that `regex` matches.
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    let searcher = Searcher::new(matcher::Regex::new(regex.clone()));
+
+    selected_lines(&searcher, contents)
+        .into_iter()
+        .map(|(_, line)| line)
         .collect()
 }
 
@@ -99,4 +504,211 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn fuzzy() {
+        let query = "prodcutive";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(
+            vec!["safe, fast, productive."],
+            search_fuzzy(query, 2, contents)
+        );
+        assert!(search_fuzzy(query, 1, contents).is_empty());
+    }
+
+    #[test]
+    fn fuzzy_ranks_by_distance() {
+        let query = "trust";
+        let contents = "\
+Rust:
+Trust me.
+safe, fast, productive.
+trust";
+
+        assert_eq!(
+            vec!["trust", "Trust me."],
+            search_fuzzy(query, 1, contents)
+        );
+        assert_eq!(
+            vec!["trust", "Trust me.", "Rust:"],
+            search_fuzzy(query, 2, contents)
+        );
+    }
+
+    #[test]
+    fn case_insensitive_unicode() {
+        let query = "GRÜSSE";
+        let contents = "\
+Viele Grüße
+Grusse
+ΣΊΣΥΦΟΣ
+grüsse";
+
+        assert_eq!(
+            vec!["Viele Grüße", "grüsse"],
+            search_case_insensitive(query, contents)
+        );
+        assert_eq!(
+            vec!["ΣΊΣΥΦΟΣ"],
+            search_case_insensitive("σίσυφος", contents)
+        );
+    }
+
+    #[test]
+    fn regex_pattern() {
+        let regex = Regex::new(r"^\d{2}:\d{2} E\d+").unwrap();
+        let contents = "\
+09:15 I100 started
+09:16 E042 disk full
+see E042 above
+10:01 E7 timeout";
+
+        assert_eq!(
+            vec!["09:16 E042 disk full", "10:01 E7 timeout"],
+            search_regex(&regex, contents)
+        );
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let args = testing::args(&["--regex", "(unclosed", "poem.txt"]);
+
+        assert!(matches!(Config::new(args), Err(Error::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn inverted_selection_keeps_line_numbers() {
+        let config = testing::config(&["-v", "e", "-"]);
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.";
+
+        assert_eq!(
+            vec![Match {
+                line_number: 1,
+                byte_offset: 0,
+                line: "Rust:",
+                spans: Vec::new(),
+            }],
+            find_matches(&config, contents)
+        );
+    }
+
+    #[test]
+    fn matches_carry_spans() {
+        let config = testing::config(&["-i", "t", "-"]);
+        let found = find_matches(&config, "Rust:\nPick three.\nTrust me.");
+
+        assert_eq!(
+            vec![vec![3..4], vec![5..6], vec![0..1, 4..5]],
+            found.into_iter().map(|m| m.spans).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn matches_carry_byte_offsets() {
+        let contents = "Rust:\r\nsafe, fast, productive.\nPick three.";
+
+        assert_eq!(
+            vec![(2, 7), (3, 31)],
+            matching_lines(contents, |line| line.contains('e'))
+                .map(|m| (m.line_number, m.byte_offset))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        let outcome = |matched, errors| Outcome { matched, errors };
+
+        assert_eq!(0, outcome(true, 0).exit_code());
+        assert_eq!(1, outcome(false, 0).exit_code());
+        assert_eq!(2, outcome(true, 1).exit_code());
+        assert_eq!(2, outcome(false, 3).exit_code());
+    }
+
+    #[test]
+    fn invalid_utf8_has_its_own_error() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "bad bytes");
+        assert!(matches!(
+            Error::reading("poem.txt", err),
+            Error::InvalidUtf8 { .. }
+        ));
+
+        let err = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert!(matches!(Error::reading("poem.txt", err), Error::Io { .. }));
+    }
+
+    #[test]
+    fn mapped_files_print_the_same_as_buffered_ones() {
+        let path = testing::temp_path("mmap.txt");
+        let mut contents = Vec::new();
+        for i in 0..50_000 {
+            let line = if i % 997 == 0 {
+                "a needle"
+            } else {
+                "hay in the haystack"
+            };
+            contents.extend(format!("{} {}\n", i, line).into_bytes());
+        }
+        assert!(contents.len() as u64 >= MMAP_THRESHOLD);
+
+        let search = |args: &[&str]| {
+            let mut all = args.to_vec();
+            all.push(path.to_str().unwrap());
+            let config = testing::config(&all);
+            let printer = Printer::new(&config, false, false);
+            let input = Input {
+                path: Some(path.clone()),
+                walked: false,
+            };
+
+            let mut mapped = Vec::new();
+            let result = search_input(&config, &printer, &input, &mut mapped);
+            let mapped_error = result.err().map(|e| e.to_string());
+
+            let mut buffered = Vec::new();
+            let reader = BufReader::new(File::open(&path).unwrap());
+            let result =
+                search_buffered(&printer, &input, reader, &mut buffered);
+            let buffered_error = result.err().map(|e| e.to_string());
+
+            assert_eq!(
+                String::from_utf8_lossy(&buffered),
+                String::from_utf8_lossy(&mapped)
+            );
+            assert_eq!(buffered_error, mapped_error);
+            (String::from_utf8(mapped).unwrap(), mapped_error)
+        };
+
+        fs::write(&path, &contents).unwrap();
+        let (output, error) = search(&["-n", "needle"]);
+        assert!(output.starts_with("1:0 a needle\n998:997 a needle\n"));
+        assert_eq!(None, error);
+        search(&["-C", "1", "needle"]);
+        assert_eq!(("51\n".to_string(), None), search(&["-c", "needle"]));
+
+        contents.extend(b"last needle\n\xff\xfe\nneedle after\n");
+        fs::write(&path, &contents).unwrap();
+        let (output, error) = search(&["needle"]);
+        assert!(output.ends_with("49850 a needle\nlast needle\n"));
+        assert!(error.is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn binary_files_are_detected() {
+        let mut text: &[u8] = b"Rust:\nsafe, fast, productive.";
+        let mut binary: &[u8] = b"ELF\0\0rust";
+
+        assert!(!looks_binary(&mut text).unwrap());
+        assert!(looks_binary(&mut binary).unwrap());
+    }
 }
@@ -1,16 +1,36 @@
 use std::env;
 use std::process;
 
-use minigrep::Config;
+use minigrep::{Command, USAGE};
 
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-    });
+    let config = match Command::parse(env::args()) {
+        Ok(Command::Search(config)) => *config,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("minigrep {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("Problem parsing arguments: {}", err);
+            eprint!("\n{}", USAGE);
+            process::exit(2);
+        }
+    };
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    if config.debug_config {
+        eprint!("{}", config.describe());
+    }
+
+    // Like grep: 0 if a line was selected, 1 if none was, 2 on any error.
+    match minigrep::run(config) {
+        Ok(outcome) => process::exit(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(2);
+        }
     }
 }
@@ -0,0 +1,644 @@
+//! How patterns are found within a line.
+//!
+//! Anything that implements [`Matcher`] can drive a
+//! [`Searcher`](crate::Searcher). The matchers here cover what the command
+//! line can ask for, and [`from_config`] picks the right one for a
+//! `Config`.
+
+use std::iter;
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use caseless::Caseless;
+
+use crate::Config;
+
+/// Finds matches within a single line, without its line ending.
+pub trait Matcher {
+    /// The first match in `line` that starts at or after byte `start`,
+    /// which must be on a character boundary.
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Every non-overlapping, non-empty match in `line`, in order.
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+
+        while let Some(span) = self.find_at(line, start) {
+            start = if span.is_empty() {
+                // Step over one character so an empty match can't repeat.
+                match line[span.end..].chars().next() {
+                    Some(c) => span.end + c.len_utf8(),
+                    None => break,
+                }
+            } else {
+                span.end
+            };
+
+            if !span.is_empty() {
+                spans.push(span);
+            }
+        }
+
+        spans
+    }
+
+    /// The string this matcher looks for, if it matches exactly that
+    /// string and nothing else, case-sensitively. A `Searcher` can then
+    /// look for it with a faster substring search.
+    fn as_literal(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        (**self).find_at(line, start)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        (**self).is_match(line)
+    }
+
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        (**self).spans(line)
+    }
+
+    fn as_literal(&self) -> Option<&str> {
+        (**self).as_literal()
+    }
+}
+
+/// The matcher for `config`'s patterns, prepared once per search.
+pub fn from_config(config: &Config) -> Box<dyn Matcher + Send + Sync> {
+    for_patterns(
+        config,
+        &config.patterns,
+        config.case_sensitive,
+        config.regex.as_ref(),
+    )
+}
+
+/// The matcher `config` asks for, but for `patterns` and `case_sensitive`
+/// instead of its own, with `regex` compiled from them in `-E` mode.
+/// `--interactive` builds one each time the query changes.
+pub(crate) fn for_patterns(
+    config: &Config,
+    patterns: &[String],
+    case_sensitive: bool,
+    regex: Option<&regex::Regex>,
+) -> Box<dyn Matcher + Send + Sync> {
+    let matcher = inner(config, patterns, case_sensitive, regex);
+
+    // A regular expression is anchored for -x when it's built. Whole lines
+    // are whole words too, so -x wins over -w.
+    if config.line_regexp {
+        match regex {
+            Some(_) => matcher,
+            None => Box::new(WholeLine::new(matcher)),
+        }
+    } else if config.word_regexp {
+        Box::new(Word::new(matcher))
+    } else {
+        matcher
+    }
+}
+
+/// The matcher for the patterns themselves, wherever they are.
+fn inner(
+    config: &Config,
+    patterns: &[String],
+    case_sensitive: bool,
+    regex: Option<&regex::Regex>,
+) -> Box<dyn Matcher + Send + Sync> {
+    if let Some(max_distance) = config.fuzzy {
+        return Box::new(Fuzzy::new(patterns, max_distance, case_sensitive));
+    }
+
+    match (regex, patterns) {
+        (Some(regex), _) => Box::new(Regex::new(regex.clone())),
+        (None, [query]) if case_sensitive => Box::new(Literal::new(query)),
+        (None, [query]) => Box::new(CaseInsensitive::new(query)),
+        (None, patterns) => Box::new(Literals::new(patterns, case_sensitive)),
+    }
+}
+
+/// Matches a string exactly.
+pub struct Literal {
+    query: String,
+}
+
+impl Literal {
+    pub fn new(query: &str) -> Literal {
+        Literal {
+            query: query.to_string(),
+        }
+    }
+}
+
+impl Matcher for Literal {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        line[start..]
+            .find(self.query.as_str())
+            .map(|i| start + i..start + i + self.query.len())
+    }
+
+    fn as_literal(&self) -> Option<&str> {
+        Some(&self.query)
+    }
+}
+
+/// Matches a string in any case, with full Unicode case folding.
+pub struct CaseInsensitive {
+    /// The query after case folding.
+    folded: Vec<char>,
+}
+
+impl CaseInsensitive {
+    pub fn new(query: &str) -> CaseInsensitive {
+        CaseInsensitive {
+            folded: query.chars().default_case_fold().collect(),
+        }
+    }
+}
+
+impl Matcher for CaseInsensitive {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        line[start..]
+            .char_indices()
+            .map(|(i, _)| start + i)
+            .chain(Some(line.len()))
+            .find_map(|i| {
+                caseless_prefix(&self.folded, &line[i..]).map(|len| i..i + len)
+            })
+    }
+}
+
+/// Matches a regular expression.
+pub struct Regex {
+    regex: regex::Regex,
+}
+
+impl Regex {
+    pub fn new(regex: regex::Regex) -> Regex {
+        Regex { regex }
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.regex.find_at(line, start).map(|m| m.range())
+    }
+}
+
+/// Matches any of several strings.
+pub struct Literals {
+    inner: LiteralsInner,
+}
+
+enum LiteralsInner {
+    /// All found in a single pass over the line.
+    Automaton(AhoCorasick),
+    /// Each looked for in turn.
+    Folded(Vec<CaseInsensitive>),
+    /// ASCII patterns, ignoring case: in one pass over an ASCII line, but
+    /// each in turn over any other, where something like the Kelvin sign
+    /// can fold to ASCII.
+    Ascii {
+        automaton: AhoCorasick,
+        folded: Vec<CaseInsensitive>,
+    },
+}
+
+impl Literals {
+    /// A matcher for any of `patterns`.
+    ///
+    /// Aho-Corasick handles them in one pass when matching is
+    /// case-sensitive, or when every pattern and the line are ASCII, so
+    /// ASCII case insensitivity is enough. Otherwise each pattern gets the
+    /// full Unicode case folding a single query would.
+    pub fn new(patterns: &[String], case_sensitive: bool) -> Literals {
+        let automaton = || {
+            AhoCorasickBuilder::new()
+                .match_kind(MatchKind::LeftmostLongest)
+                .ascii_case_insensitive(!case_sensitive)
+                .build(patterns)
+                .expect("literal patterns always build an automaton")
+        };
+        let folded = || patterns.iter().map(|p| CaseInsensitive::new(p));
+
+        let inner = if case_sensitive {
+            LiteralsInner::Automaton(automaton())
+        } else if patterns.iter().all(|p| p.is_ascii()) {
+            LiteralsInner::Ascii {
+                automaton: automaton(),
+                folded: folded().collect(),
+            }
+        } else {
+            LiteralsInner::Folded(folded().collect())
+        };
+
+        Literals { inner }
+    }
+}
+
+impl Matcher for Literals {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        match &self.inner {
+            LiteralsInner::Automaton(automaton) => {
+                find_any(automaton, line, start)
+            }
+            LiteralsInner::Ascii { automaton, .. } if line.is_ascii() => {
+                find_any(automaton, line, start)
+            }
+            LiteralsInner::Folded(folded)
+            | LiteralsInner::Ascii { folded, .. } => folded
+                .iter()
+                .filter_map(|matcher| matcher.find_at(line, start))
+                // The leftmost match wins, and the longest of those.
+                .min_by_key(|span| (span.start, usize::MAX - span.end)),
+        }
+    }
+}
+
+fn find_any(
+    automaton: &AhoCorasick,
+    line: &str,
+    start: usize,
+) -> Option<Range<usize>> {
+    automaton
+        .find(aho_corasick::Input::new(line).range(start..))
+        .map(|m| m.range())
+}
+
+/// Matches any substring within a Levenshtein distance of the query: one
+/// that at most `max_distance` insertions, deletions or substitutions of a
+/// single character turn into it.
+pub struct Fuzzy {
+    queries: Vec<Vec<char>>,
+    max_distance: usize,
+    case_sensitive: bool,
+}
+
+impl Fuzzy {
+    /// A matcher for anything within `max_distance` of any of `patterns`.
+    /// It should be less than the length of the shortest of them, or every
+    /// line matches.
+    pub fn new(
+        patterns: &[String],
+        max_distance: usize,
+        case_sensitive: bool,
+    ) -> Fuzzy {
+        Fuzzy {
+            queries: patterns.iter().map(|p| p.chars().collect()).collect(),
+            max_distance,
+            case_sensitive,
+        }
+    }
+
+    /// The smallest distance between any query and any part of `line`, if
+    /// it's within the maximum.
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        let text: Vec<(usize, char)> = line.char_indices().collect();
+
+        self.queries
+            .iter()
+            .filter_map(|query| {
+                self.alignments(query, &text).map(|(cost, _)| cost).min()
+            })
+            .filter(|&cost| cost <= self.max_distance)
+            .min()
+    }
+
+    /// The first match in `line` at or after byte `start`, with its
+    /// distance from the query it matches.
+    pub fn find_with_distance(
+        &self,
+        line: &str,
+        start: usize,
+    ) -> Option<(Range<usize>, usize)> {
+        let text: Vec<(usize, char)> = line[start..]
+            .char_indices()
+            .map(|(i, c)| (start + i, c))
+            .collect();
+        let offset = |i: usize| text.get(i).map_or(line.len(), |&(at, _)| at);
+
+        // The leftmost match wins, and the closest of those.
+        self.queries
+            .iter()
+            .filter_map(|query| self.first_match(query, &text))
+            .min_by_key(|&(from, _, cost)| (from, cost))
+            .map(|(from, to, cost)| (offset(from)..offset(to), cost))
+    }
+
+    /// The first match of `query` in `text`, as the indexes of its first
+    /// and one past its last character, and its distance.
+    ///
+    /// The match ends at the first character where the distance is within
+    /// the maximum, or further on if that keeps bringing the distance down,
+    /// so `frog` matches all of `frog` rather than just `fro`.
+    fn first_match(
+        &self,
+        query: &[char],
+        text: &[(usize, char)],
+    ) -> Option<(usize, usize, usize)> {
+        let mut best: Option<(usize, usize, usize)> = None;
+
+        for (cost, (from, to)) in self.alignments(query, text) {
+            match best {
+                Some((_, _, best_cost)) if cost < best_cost => {
+                    best = Some((from, to, cost))
+                }
+                Some(_) => break,
+                None if cost <= self.max_distance => {
+                    best = Some((from, to, cost))
+                }
+                None => {}
+            }
+        }
+
+        best
+    }
+
+    /// For each character of `text` in turn, the distance between `query`
+    /// and the closest substring ending with that character, and where that
+    /// substring starts and ends. This is Sellers' variant of the
+    /// Levenshtein algorithm, with a column of the table per character.
+    fn alignments<'q>(
+        &'q self,
+        query: &'q [char],
+        text: &'q [(usize, char)],
+    ) -> impl Iterator<Item = (usize, (usize, usize))> + 'q {
+        // For each prefix of the query, its distance from the closest
+        // substring ending here, and where that substring starts. Any
+        // position can start a match, so the empty prefix costs nothing.
+        let mut column: Vec<(usize, usize)> =
+            (0..=query.len()).map(|i| (i, 0)).collect();
+
+        text.iter().enumerate().map(move |(j, &(_, c))| {
+            let mut diagonal = column[0];
+            column[0] = (0, j + 1);
+
+            for i in 1..=query.len() {
+                let substitute = (
+                    diagonal.0 + usize::from(!self.same(query[i - 1], c)),
+                    diagonal.1,
+                );
+                let skip_query = (column[i - 1].0 + 1, column[i - 1].1);
+                let skip_text = (column[i].0 + 1, column[i].1);
+
+                diagonal = column[i];
+                column[i] = [skip_query, skip_text].into_iter().fold(
+                    substitute,
+                    |best, next| {
+                        if next.0 < best.0 {
+                            next
+                        } else {
+                            best
+                        }
+                    },
+                );
+            }
+
+            let (cost, from) = column[query.len()];
+            (cost, (from, j + 1))
+        })
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b
+            || (!self.case_sensitive && a.to_lowercase().eq(b.to_lowercase()))
+    }
+}
+
+impl Matcher for Fuzzy {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.find_with_distance(line, start).map(|(span, _)| span)
+    }
+}
+
+/// Matches what another matcher does, but only as a whole word: with no
+/// letter, digit or `_` just before or just after it.
+pub struct Word<M> {
+    inner: M,
+}
+
+impl<M> Word<M> {
+    pub fn new(inner: M) -> Word<M> {
+        Word { inner }
+    }
+}
+
+impl<M: Matcher> Matcher for Word<M> {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        let mut from = start;
+
+        loop {
+            let span = self.inner.find_at(line, from)?;
+            let before = line[..span.start].chars().next_back();
+            let after = line[span.end..].chars().next();
+            if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                return Some(span);
+            }
+
+            // `foo` in `foobar foo` only counts the second time, so look
+            // again from the next character on.
+            let c = line[span.start..].chars().next()?;
+            from = span.start + c.len_utf8();
+        }
+    }
+}
+
+/// Matches what another matcher does, but only when that's a whole line.
+/// In text of several lines, as `-U` searches, that's from the start of
+/// any line to the end of the same or a later one.
+pub struct WholeLine<M> {
+    inner: M,
+}
+
+impl<M> WholeLine<M> {
+    pub fn new(inner: M) -> WholeLine<M> {
+        WholeLine { inner }
+    }
+}
+
+impl<M: Matcher> Matcher for WholeLine<M> {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        let mut from = start;
+
+        loop {
+            let span = self.inner.find_at(line, from)?;
+            let starts_line = line[..span.start].ends_with('\n');
+            let rest = &line[span.end..];
+            if (span.start == 0 || starts_line)
+                && (rest.is_empty()
+                    || rest.starts_with('\n')
+                    || rest.starts_with("\r\n"))
+            {
+                return Some(span);
+            }
+
+            // Nothing else starting on the same line can match it whole.
+            from = span.start + line[span.start..].find('\n')? + 1;
+        }
+    }
+}
+
+/// Whether `c` can be part of a word, for `-w`.
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// If `text` starts with the case-folded `query` once `text` is folded too,
+/// the length in bytes of that prefix of `text`.
+///
+/// Full Unicode case folding is used, so `ß` matches `ss` and `Σ`, `σ` and
+/// a final `ς` all match each other. Each character of `text` is folded as
+/// it's reached, so nothing is allocated. A match has to cover whole
+/// characters of `text`: `s` alone doesn't match inside `ß`.
+fn caseless_prefix(query: &[char], text: &str) -> Option<usize> {
+    let mut query = query.iter().peekable();
+
+    for (i, c) in text.char_indices() {
+        if query.peek().is_none() {
+            return Some(i);
+        }
+        for folded in iter::once(c).default_case_fold() {
+            if query.next() != Some(&folded) {
+                return None;
+            }
+        }
+    }
+
+    match query.peek() {
+        None => Some(text.len()),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_spans() {
+        let matcher = Literal::new("to");
+
+        assert_eq!(vec![0..2, 4..6, 7..9], matcher.spans("tomato toast"));
+    }
+
+    #[test]
+    fn case_insensitive_spans_use_original_offsets() {
+        let matcher = CaseInsensitive::new("rust");
+
+        assert_eq!(vec![0..4, 7..11], matcher.spans("Rust, trUST"));
+        // "É" is two bytes, so the match starts at byte 2, not 1.
+        let matcher = CaseInsensitive::new("la");
+        assert_eq!(vec![2..4], matcher.spans("ÉLAN"));
+    }
+
+    #[test]
+    fn full_case_folding() {
+        let matcher = CaseInsensitive::new("strasse");
+        assert_eq!(vec![0..7, 9..16], matcher.spans("Straße, STRASSE"));
+
+        let matcher = CaseInsensitive::new("ὈΔΥΣΣΕΎΣ");
+        assert!(matcher.is_match("ὀδυσσεύς"));
+
+        // The Turkish capital dotted I folds to "i" plus a combining dot.
+        let matcher = CaseInsensitive::new("İstanbul");
+        assert!(matcher.is_match("i\u{307}stanbul"));
+        assert!(!matcher.is_match("istanbul"));
+
+        let matcher = CaseInsensitive::new("s");
+        assert!(!matcher.is_match("ß"));
+    }
+
+    #[test]
+    fn many_literals() {
+        let patterns = ["E042", "timeout", "disk"].map(String::from);
+
+        let matcher = Literals::new(&patterns, true);
+        assert_eq!(vec![0..4, 10..14], matcher.spans("E042 full disk"));
+        assert!(!matcher.is_match("TIMEOUT"));
+
+        let matcher = Literals::new(&patterns, false);
+        assert_eq!(vec![4..11], matcher.spans("io: TimeOut"));
+    }
+
+    #[test]
+    fn many_literals_fold_unicode_case() {
+        let patterns = ["straße", "Ωmega"].map(String::from);
+        let matcher = Literals::new(&patterns, false);
+
+        assert!(matches!(matcher.inner, LiteralsInner::Folded(_)));
+        assert_eq!(vec![4..11, 12..18], matcher.spans("die STRASSE ωMEGA"));
+    }
+
+    #[test]
+    fn many_ascii_literals_still_fold_other_text() {
+        let patterns = ["kelvin", "mess"].map(String::from);
+        let matcher = Literals::new(&patterns, false);
+
+        assert!(matches!(matcher.inner, LiteralsInner::Ascii { .. }));
+        assert_eq!(vec![4..10], matcher.spans("0 K KELVIN"));
+        // The Kelvin sign and the long s, which fold to k and s.
+        let line = "0 \u{212A} \u{212A}elvin me\u{17F}s";
+        assert_eq!(vec![6..14, 15..20], matcher.spans(line));
+        for pattern in &patterns {
+            assert!(CaseInsensitive::new(pattern).is_match(line));
+        }
+    }
+
+    #[test]
+    fn fuzzy_spans_cover_the_approximate_match() {
+        let matcher = Fuzzy::new(&["frog".to_string()], 1, true);
+
+        assert_eq!(vec![2..5, 12..16], matcher.spans("a frg and a frog"));
+        assert_eq!(Some((2..6, 1)), matcher.find_with_distance("a frag", 0));
+        assert_eq!(Some(1), matcher.distance("a frg and a Frog"));
+        assert_eq!(None, matcher.distance("a fig"));
+    }
+
+    #[test]
+    fn fuzzy_matching_can_ignore_case() {
+        let matcher = Fuzzy::new(&["NOBODY".to_string()], 1, false);
+
+        assert_eq!(vec![6..11], matcher.spans("I'm a nobdy!"));
+        assert_eq!(Some(0), matcher.distance("Nobody"));
+        assert_eq!(Some(1), matcher.distance("ÉnoBOD"));
+    }
+
+    #[test]
+    fn whole_words() {
+        let matcher = Word::new(Literal::new("foo"));
+
+        assert_eq!(vec![7..10, 17..20], matcher.spans("foobar foo foo_ (foo)"));
+        assert!(!matcher.is_match("foo_bar barfoo"));
+
+        let matcher = Word::new(CaseInsensitive::new("café"));
+        assert_eq!(vec![0..5], matcher.spans("CAFÉ cafés"));
+    }
+
+    #[test]
+    fn whole_lines() {
+        let matcher = WholeLine::new(Literal::new("foo"));
+
+        assert!(matcher.is_match("foo"));
+        assert!(!matcher.is_match("foo bar"));
+        assert!(!matcher.is_match("a foo"));
+        assert_eq!(vec![9..12], matcher.spans("foo bar\r\nfoo\nfoo bar"));
+    }
+
+    #[test]
+    fn empty_regex_matches_have_no_spans() {
+        let matcher = Regex::new(regex::Regex::new("x*").unwrap());
+
+        assert!(matcher.is_match("abc"));
+        assert_eq!(vec![1..3], matcher.spans("axxc"));
+    }
+}
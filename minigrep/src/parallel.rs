@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Run `work` on every item using up to `threads` worker threads, and hand
+/// the results to `report` in the same order as `items`.
+///
+/// A result that finishes early waits until everything before it has been
+/// reported. If `report` returns an error, the workers stop picking up new
+/// items and that error is returned.
+pub fn ordered_map<I, T, E>(
+    items: &[I],
+    threads: usize,
+    work: impl Fn(&I) -> T + Sync,
+    mut report: impl FnMut(&I, T) -> Result<(), E>,
+) -> Result<(), E>
+where
+    I: Sync,
+    T: Send,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                // The receiver is only gone once reporting has failed.
+                if sender.send((index, work(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        let mut outcome = Ok(());
+
+        'receive: for (index, result) in receiver.iter() {
+            pending.insert(index, result);
+
+            while let Some(result) = pending.remove(&expected) {
+                if let Err(e) = report(&items[expected], result) {
+                    outcome = Err(e);
+                    break 'receive;
+                }
+                expected += 1;
+            }
+        }
+
+        // Make any worker still running fail its next send and stop.
+        next.store(items.len(), Ordering::Relaxed);
+        drop(receiver);
+        outcome
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_come_back_in_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let mut seen = Vec::new();
+
+        let result: Result<(), ()> = ordered_map(
+            &items,
+            4,
+            |&n| {
+                // Make early items finish last.
+                thread::sleep(Duration::from_millis(20 - n));
+                n * 10
+            },
+            |&n, result| {
+                assert_eq!(n * 10, result);
+                seen.push(n);
+                Ok(())
+            },
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(items, seen);
+    }
+
+    #[test]
+    fn reporting_errors_stop_the_run() {
+        let items: Vec<u32> = (0..100).collect();
+        let mut reported = 0;
+
+        let result = ordered_map(
+            &items,
+            3,
+            |&n| n,
+            |_, n| {
+                reported += 1;
+                if n == 5 {
+                    Err("stop")
+                } else {
+                    Ok(())
+                }
+            },
+        );
+
+        assert_eq!(Err("stop"), result);
+        assert_eq!(6, reported);
+    }
+}
@@ -0,0 +1,444 @@
+use std::io::{self, BufRead, Write};
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+use crate::matcher::Fuzzy;
+use crate::{json, replace, search_reader, search_text, Config, Line, Match};
+
+const FILE_NAME: &str = "\x1b[35m";
+const LINE_NUMBER: &str = "\x1b[32m";
+const SEPARATOR: &str = "\x1b[36m";
+const MATCH: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Counts of what a search found, per input and in total.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stats {
+    pub(crate) searches: usize,
+    pub(crate) searches_with_match: usize,
+    pub(crate) matched_lines: usize,
+    pub(crate) matches: usize,
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+/// Writes search results the way grep does, optionally in color.
+pub(crate) struct Printer<'a> {
+    config: &'a Config,
+    show_names: bool,
+    color: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(
+        config: &'a Config,
+        show_names: bool,
+        color: bool,
+    ) -> Printer<'a> {
+        Printer {
+            config,
+            show_names,
+            color,
+        }
+    }
+
+    /// Search one input and print what it selects.
+    pub(crate) fn print_reader(
+        &self,
+        name: &str,
+        reader: impl BufRead,
+        out: &mut impl Write,
+    ) -> io::Result<Stats> {
+        self.print(name, out, |emit| search_reader(self.config, reader, emit))
+    }
+
+    /// Search one input that's all in memory and print what it selects.
+    pub(crate) fn print_text(
+        &self,
+        name: &str,
+        text: &str,
+        out: &mut impl Write,
+    ) -> io::Result<Stats> {
+        self.print(name, out, |emit| search_text(self.config, text, emit))
+    }
+
+    /// Print what `search` selects, passing it the function that prints
+    /// each line.
+    fn print(
+        &self,
+        name: &str,
+        out: &mut impl Write,
+        search: impl FnOnce(
+            &mut dyn FnMut(Line<'_>) -> io::Result<()>,
+        ) -> io::Result<usize>,
+    ) -> io::Result<Stats> {
+        let start = Instant::now();
+        let mut matches = 0;
+
+        if self.config.json {
+            json::begin(out, name)?;
+        }
+
+        // With --fuzzy, matches are held back to be printed closest first,
+        // by distance and then line number. Lines selected by -v aren't
+        // within any distance, so they're printed in order as they come.
+        let ranking = self.config.fuzzy.filter(|_| !self.config.invert_match);
+        let fuzzy = ranking.map(|max_distance| {
+            Fuzzy::new(
+                &self.config.patterns,
+                max_distance,
+                self.config.case_sensitive,
+            )
+        });
+        let mut ranked = Vec::new();
+
+        let count = search(&mut |line| {
+            if let Line::Match(m) = &line {
+                matches += m.spans.len();
+            }
+
+            match (&fuzzy, line) {
+                (Some(fuzzy), Line::Match(m)) => {
+                    let distance = fuzzy.distance(m.line);
+                    ranked.push((
+                        distance,
+                        m.line_number,
+                        m.byte_offset,
+                        m.line.to_string(),
+                        m.spans,
+                    ));
+                    Ok(())
+                }
+                (Some(_), _) => Ok(()),
+                (None, line) => self.print_line(out, name, line),
+            }
+        })?;
+
+        ranked.sort_by_key(|&(distance, line_number, ..)| {
+            (distance, line_number)
+        });
+        for (_, line_number, byte_offset, line, spans) in ranked {
+            let m = Match {
+                line_number,
+                byte_offset,
+                line: &line,
+                spans,
+            };
+            self.print_line(out, name, Line::Match(m))?;
+        }
+
+        let stats = Stats {
+            searches: 1,
+            searches_with_match: usize::from(count > 0),
+            matched_lines: count,
+            matches,
+        };
+
+        if self.config.json {
+            json::end(out, name, &stats, start.elapsed())?;
+        } else if self.config.count {
+            if self.show_names {
+                self.styled(out, FILE_NAME, name)?;
+                self.styled(out, SEPARATOR, ":")?;
+            }
+            writeln!(out, "{}", count)?;
+        }
+
+        Ok(stats)
+    }
+
+    fn print_line(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        line: Line<'_>,
+    ) -> io::Result<()> {
+        if self.config.json {
+            return json::line(out, name, &line);
+        }
+        if self.config.count {
+            return Ok(());
+        }
+
+        // Like grep, `:` marks a selected line and `-` a line of context.
+        match line {
+            Line::Match(m) => {
+                self.prefix(out, name, m.line_number, ':')?;
+                self.highlighted(out, name, &m)?;
+            }
+            Line::Context { line_number, line } => {
+                self.prefix(out, name, line_number, '-')?;
+                out.write_all(line.as_bytes())?;
+            }
+            Line::Break => self.styled(out, SEPARATOR, "--")?,
+        }
+        writeln!(out)
+    }
+
+    /// Write the record that closes `--json` output. Other formats have no
+    /// summary.
+    pub(crate) fn summary(
+        &self,
+        out: &mut impl Write,
+        totals: &Stats,
+        elapsed: Duration,
+    ) -> io::Result<()> {
+        if self.config.json {
+            json::summary(out, totals, elapsed)?;
+        }
+        Ok(())
+    }
+
+    /// Write the `--` that goes between the output of two files.
+    pub(crate) fn file_break(&self, out: &mut impl Write) -> io::Result<()> {
+        self.styled(out, SEPARATOR, "--")?;
+        writeln!(out)
+    }
+
+    fn prefix(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        line_number: usize,
+        separator: char,
+    ) -> io::Result<()> {
+        let separator = separator.to_string();
+
+        if self.show_names {
+            self.styled(out, FILE_NAME, name)?;
+            self.styled(out, SEPARATOR, &separator)?;
+        }
+        if self.config.line_number {
+            self.styled(out, LINE_NUMBER, &line_number.to_string())?;
+            self.styled(out, SEPARATOR, &separator)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the line `m` selected with each of its spans highlighted, or
+    /// with `--replace`, replaced and the replacements highlighted.
+    ///
+    /// A match from `-U` can hold several lines; each after the first gets
+    /// its own prefix, numbered by where it is in the input, so text that
+    /// a replacement removes still counts.
+    fn highlighted(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        m: &Match<'_>,
+    ) -> io::Result<()> {
+        let line = m.line;
+        let replacements = self
+            .config
+            .replace
+            .as_ref()
+            .map(|_| replace::replacements(self.config, line, &m.spans));
+        let mut line_number = m.line_number;
+        let mut written = 0;
+
+        for (i, span) in m.spans.iter().enumerate() {
+            let before = &line[written..span.start];
+            self.lines(out, name, &mut line_number, before, None)?;
+            match &replacements {
+                Some(replacements) => {
+                    self.styled(out, MATCH, &replacements[i])?;
+                    line_number += line[span.clone()].matches('\n').count();
+                }
+                None => {
+                    let text = &line[span.clone()];
+                    self.lines(out, name, &mut line_number, text, Some(MATCH))?;
+                }
+            }
+            written = span.end;
+        }
+
+        self.lines(out, name, &mut line_number, &line[written..], None)
+    }
+
+    /// Write `text`, in `style` if there is one, with the prefix for the
+    /// next line after each newline in it.
+    fn lines(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        line_number: &mut usize,
+        text: &str,
+        style: Option<&str>,
+    ) -> io::Result<()> {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+                *line_number += 1;
+                self.prefix(out, name, *line_number, ':')?;
+            }
+            match style {
+                Some(style) if !part.is_empty() => {
+                    self.styled(out, style, part)?
+                }
+                _ => out.write_all(part.as_bytes())?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn styled(
+        &self,
+        out: &mut impl Write,
+        style: &str,
+        text: &str,
+    ) -> io::Result<()> {
+        if self.color {
+            write!(out, "{}{}{}", style, text, RESET)
+        } else {
+            out.write_all(text.as_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(args: &[&str], color: bool, contents: &str) -> String {
+        let config = crate::testing::config(args);
+        let mut out = Vec::new();
+
+        Printer::new(&config, true, color)
+            .print_reader("poem.txt", contents.as_bytes(), &mut out)
+            .unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_output() {
+        assert_eq!(
+            "poem.txt:2:Are you nobody, too?\n",
+            printed(
+                &["-n", "too", "-"],
+                false,
+                "Who are you?\nAre you nobody, too?\n"
+            )
+        );
+    }
+
+    #[test]
+    fn fuzzy_matches_are_ranked_but_inverted_ones_arent() {
+        let contents = "Rust:\nTrust me.\nsafe, fast, productive.\ntrust\n";
+
+        assert_eq!(
+            "poem.txt:4:trust\npoem.txt:2:Trust me.\n",
+            printed(&["-n", "--fuzzy", "1", "trust", "-"], false, contents)
+        );
+        assert_eq!(
+            "poem.txt:1:Rust:\npoem.txt:3:safe, fast, productive.\n",
+            printed(
+                &["-n", "-v", "--fuzzy", "1", "trust", "-"],
+                false,
+                contents
+            )
+        );
+    }
+
+    #[test]
+    fn huge_context_is_only_as_big_as_the_input() {
+        let contents = "one\ntwo\nthree\n";
+        let huge = "99999999999999";
+
+        assert_eq!(
+            "poem.txt-one\npoem.txt:two\n",
+            printed(&["-B", huge, "two", "-"], false, contents)
+        );
+        assert_eq!(
+            "poem.txt:two\npoem.txt-three\n",
+            printed(&["-A", huge, "two", "-"], false, contents)
+        );
+        let all = "poem.txt-one\npoem.txt:two\npoem.txt-three\n";
+        assert_eq!(all, printed(&["-C", huge, "two", "-"], false, contents));
+        assert_eq!(
+            all,
+            printed(&["-U", "-C", huge, "two", "-"], false, contents)
+        );
+    }
+
+    #[test]
+    fn json_records() {
+        let out = printed(&["--json", "to", "-"], false, "nobody, too?\n");
+        let records: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(3, records.len());
+        assert_eq!("begin", records[0]["type"]);
+        assert_eq!("match", records[1]["type"]);
+        assert_eq!(9, records[1]["data"]["column"]);
+        assert_eq!("nobody, too?", records[1]["data"]["line"]);
+        assert_eq!(
+            serde_json::json!([{ "text": "to", "start": 8, "end": 10 }]),
+            records[1]["data"]["submatches"]
+        );
+        assert_eq!("end", records[2]["type"]);
+        assert_eq!(1, records[2]["data"]["stats"]["matched_lines"]);
+    }
+
+    #[test]
+    fn replaced_output() {
+        assert_eq!(
+            "poem.txt:2:Are you somebody, too?\n",
+            printed(
+                &["-n", "-E", "-r", "some$1", r"no(body)", "-"],
+                false,
+                "Who are you?\nAre you nobody, too?\n"
+            )
+        );
+    }
+
+    #[test]
+    fn fuzzy_output_is_ranked() {
+        assert_eq!(
+            "poem.txt:2:Are you nobody, too?\npoem.txt:1:I'm nobdy!\n",
+            printed(
+                &["-n", "--fuzzy", "1", "nobody", "-"],
+                false,
+                "I'm nobdy!\nAre you nobody, too?\nWho are you?\n"
+            )
+        );
+    }
+
+    #[test]
+    fn multiline_matches_prefix_every_line() {
+        let contents = "Who are you?\nAre you nobody,\ntoo?\nThen\n";
+
+        assert_eq!(
+            "poem.txt:2:Are you nobody,\npoem.txt:3:too?\n",
+            printed(&["-n", "-U", "nobody,\ntoo", "-"], false, contents)
+        );
+        assert_eq!(
+            "poem.txt:2:Are you X?\n",
+            printed(
+                &["-n", "-U", "-r", "X", "nobody,\ntoo", "-"],
+                false,
+                contents
+            )
+        );
+    }
+
+    #[test]
+    fn colored_output_highlights_every_match() {
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m\x1b[36m:\x1b[0m\
+             \x1b[1;31mTo\x1b[0m \x1b[1;31mto\x1b[0mad\n",
+            printed(&["-i", "to", "-"], true, "To toad\n")
+        );
+    }
+}
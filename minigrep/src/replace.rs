@@ -0,0 +1,396 @@
+//! `--replace`, and the `--write` and `--dry-run` modes that edit files
+//! with it.
+//!
+//! Replacements are made over the same spans `find_matches` returns, so a
+//! line is only ever changed where the search would have highlighted it.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::{find_matches, Config, Match};
+
+/// Lines of unchanged text shown around each change in a diff, as
+/// `diff -u` does.
+const DIFF_CONTEXT: usize = 3;
+
+/// What each of `spans` in `line` is replaced with. With `-E`, `$1`,
+/// `${name}` and the like in the replacement are expanded from that
+/// match's capture groups; otherwise it's used as it is.
+pub(crate) fn replacements(
+    config: &Config,
+    line: &str,
+    spans: &[Range<usize>],
+) -> Vec<String> {
+    let replacement = config.replace.as_deref().unwrap_or_default();
+
+    spans
+        .iter()
+        .map(|span| match &config.regex {
+            Some(regex) => {
+                let mut expanded = String::new();
+                if let Some(captures) = regex.captures_at(line, span.start) {
+                    captures.expand(replacement, &mut expanded);
+                }
+                expanded
+            }
+            None => replacement.to_string(),
+        })
+        .collect()
+}
+
+/// `line` with each of `spans` replaced.
+pub(crate) fn replace(
+    config: &Config,
+    line: &str,
+    spans: &[Range<usize>],
+) -> String {
+    let mut replaced = String::with_capacity(line.len());
+    let mut written = 0;
+
+    for (span, replacement) in
+        spans.iter().zip(replacements(config, line, spans))
+    {
+        replaced.push_str(&line[written..span.start]);
+        replaced.push_str(&replacement);
+        written = span.end;
+    }
+
+    replaced.push_str(&line[written..]);
+    replaced
+}
+
+/// A line that `--replace` changes.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Edit<'a> {
+    /// Where the line starts in the text, counting from 0.
+    pub(crate) index: usize,
+    /// The line as it is, without its line ending.
+    pub(crate) old: &'a str,
+    /// What it becomes.
+    pub(crate) new: String,
+}
+
+/// Every line of `contents` that the search selects, and the edits that
+/// replacing their matches makes. Lines selected by `-v`, or whose
+/// replacement is the same as what it replaces, aren't edited.
+pub(crate) fn edits<'a>(
+    config: &Config,
+    contents: &'a str,
+) -> (Vec<Match<'a>>, Vec<Edit<'a>>) {
+    let matches = find_matches(config, contents);
+    let edits = matches
+        .iter()
+        .filter_map(|m| {
+            let new = replace(config, m.line, &m.spans);
+            (new != m.line).then(|| Edit {
+                index: m.line_number - 1,
+                old: m.line,
+                new,
+            })
+        })
+        .collect();
+
+    (matches, edits)
+}
+
+/// `contents` with `edits` made. Each edited line keeps its own line
+/// ending, so a file with `\r\n` endings keeps them.
+pub(crate) fn apply(contents: &str, edits: &[Edit<'_>]) -> String {
+    let mut edited = String::with_capacity(contents.len());
+    let mut edits = edits.iter().peekable();
+
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        match edits.next_if(|edit| edit.index == index) {
+            Some(edit) => {
+                edited.push_str(&edit.new);
+                edited.push_str(&raw[edit.old.len()..]);
+            }
+            None => edited.push_str(raw),
+        }
+    }
+
+    edited
+}
+
+/// Write a unified diff that turns `contents` into what `edits` make of it,
+/// the way `diff -u` would, so `patch -p0` can apply it.
+pub(crate) fn diff(
+    out: &mut impl Write,
+    name: &str,
+    contents: &str,
+    edits: &[Edit<'_>],
+) -> io::Result<()> {
+    if edits.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = contents
+        .split_inclusive('\n')
+        .map(crate::trim_newline)
+        .collect();
+    let missing_newline = !contents.ends_with('\n');
+    // How many more lines the new text has than the old before each hunk,
+    // so hunks after a multi-line replacement start in the right place.
+    let mut shift = 0isize;
+
+    writeln!(out, "--- {}", name)?;
+    writeln!(out, "+++ {}", name)?;
+
+    for hunk in hunks(edits) {
+        let first = hunk[0].index.saturating_sub(DIFF_CONTEXT);
+        let last =
+            (hunk[hunk.len() - 1].index + DIFF_CONTEXT + 1).min(lines.len());
+        let new_lines = |edit: &Edit<'_>| edit.new.split('\n').count();
+        let added: usize = hunk.iter().map(new_lines).sum();
+        let old_len = last - first;
+        let new_len = old_len - hunk.len() + added;
+
+        writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            first + 1,
+            old_len,
+            (first as isize + shift + 1),
+            new_len
+        )?;
+        shift += new_len as isize - old_len as isize;
+
+        let mut edits = hunk.iter().peekable();
+        for (index, line) in lines.iter().enumerate().take(last).skip(first) {
+            let at_end = missing_newline && index == lines.len() - 1;
+
+            match edits.next_if(|edit| edit.index == index) {
+                Some(edit) => {
+                    diff_line(out, '-', line, at_end)?;
+                    let new: Vec<&str> = edit.new.split('\n').collect();
+                    for (i, new_line) in new.iter().enumerate() {
+                        let at_end = at_end && i == new.len() - 1;
+                        diff_line(out, '+', new_line, at_end)?;
+                    }
+                }
+                None => diff_line(out, ' ', line, at_end)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split `edits` into the groups that share a hunk: those close enough
+/// that their context would touch or overlap.
+fn hunks<'e, 'a>(edits: &'e [Edit<'a>]) -> Vec<&'e [Edit<'a>]> {
+    let mut hunks = Vec::new();
+    let mut start = 0;
+
+    for i in 1..edits.len() {
+        if edits[i].index - edits[i - 1].index > 2 * DIFF_CONTEXT + 1 {
+            hunks.push(&edits[start..i]);
+            start = i;
+        }
+    }
+    hunks.push(&edits[start..]);
+
+    hunks
+}
+
+fn diff_line(
+    out: &mut impl Write,
+    marker: char,
+    line: &str,
+    at_end: bool,
+) -> io::Result<()> {
+    writeln!(out, "{}{}", marker, line)?;
+    if at_end {
+        writeln!(out, "\\ No newline at end of file")?;
+    }
+    Ok(())
+}
+
+/// Replace the file at `path` with `contents` so that anyone reading it
+/// sees either the old contents or the new, never half of each: the new
+/// contents go to a temporary file beside it, which is then renamed over
+/// it. If `path` is a symlink, the file it points to is replaced and the
+/// link is left alone.
+///
+/// The file keeps its permissions, but not its owner or group: the new one
+/// belongs to whoever runs minigrep. Other hard links to the old file keep
+/// the old contents.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let permissions = fs::metadata(&path)?.permissions();
+    let (temp, mut file) = create_temp(&path)?;
+
+    let result = file
+        .write_all(contents.as_bytes())
+        .and_then(|()| file.sync_all())
+        .and_then(|()| fs::set_permissions(&temp, permissions))
+        .and_then(|()| fs::rename(&temp, &path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Create a temporary file beside `path` that didn't exist before, so that
+/// nothing already there is written over, even by another minigrep editing
+/// the same file.
+fn create_temp(path: &Path) -> io::Result<(PathBuf, File)> {
+    const ATTEMPTS: usize = 100;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    for attempt in 0..ATTEMPTS {
+        let temp = path.with_file_name(format!(
+            ".{}.minigrep-{}-{}",
+            file_name,
+            process::id(),
+            attempt
+        ));
+        match File::options().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("no free name for a temporary file after {} tries", ATTEMPTS),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{config, temp_path};
+
+    fn diffed(args: &[&str], contents: &str) -> String {
+        let config = config(args);
+        let (_, edits) = edits(&config, contents);
+        let mut out = Vec::new();
+
+        diff(&mut out, "poem.txt", contents, &edits).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn literal_replacement() {
+        let config = config(&["-r", "frog", "toad", "-"]);
+
+        assert_eq!(
+            "a frog, a frog",
+            replace(&config, "a toad, a toad", &[2..6, 10..14])
+        );
+    }
+
+    #[test]
+    fn capture_groups_are_expanded() {
+        let config = config(&["-E", "-r", "$2 $1", r"(\w+)@(\w+)", "-"]);
+        let line = "mail ann@home or bob@work";
+        let (matches, _) = edits(&config, line);
+
+        assert_eq!(
+            vec!["home ann", "work bob"],
+            replacements(&config, line, &matches[0].spans)
+        );
+        assert_eq!(
+            "mail home ann or work bob",
+            replace(&config, line, &matches[0].spans)
+        );
+    }
+
+    #[test]
+    fn apply_keeps_line_endings() {
+        let config = config(&["-r", "toad", "frog", "-"]);
+        let contents = "frog\r\nbog\r\nfrog";
+        let (matches, edits) = edits(&config, contents);
+
+        assert_eq!(2, matches.len());
+        assert_eq!("toad\r\nbog\r\ntoad", apply(contents, &edits));
+    }
+
+    #[test]
+    fn unified_diff() {
+        let contents = "1\n2\nfrog\n4\n5\n6\n7\n8\n9\n10\n11\nfrog\n";
+
+        assert_eq!(
+            "--- poem.txt\n+++ poem.txt\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-frog\n+toad\n 4\n 5\n 6\n\
+             @@ -9,4 +9,4 @@\n 9\n 10\n 11\n-frog\n+toad\n",
+            diffed(&["-r", "toad", "frog", "-"], contents)
+        );
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let contents = "frog\n2\n3\n4\n5\n6\n7\nfrog";
+
+        assert_eq!(
+            "--- poem.txt\n+++ poem.txt\n\
+             @@ -1,8 +1,10 @@\n-frog\n+to\n+ad\n 2\n 3\n 4\n 5\n 6\n 7\n\
+             -frog\n\\ No newline at end of file\n\
+             +to\n+ad\n\\ No newline at end of file\n",
+            diffed(&["-r", "to\nad", "frog", "-"], contents)
+        );
+    }
+
+    #[test]
+    fn nothing_to_change_prints_nothing() {
+        assert_eq!("", diffed(&["-r", "frog", "frog", "-"], "frog\n"));
+        assert_eq!("", diffed(&["-v", "-r", "x", "frog", "-"], "toad\n"));
+    }
+
+    #[test]
+    fn writes_replace_the_file() {
+        let path = temp_path("replace");
+        fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, "new").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!("new", contents);
+    }
+
+    #[test]
+    fn writes_leave_other_files_alone() {
+        let dir = temp_path("temp");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("poem.txt");
+        fs::write(&path, "old").unwrap();
+        // Where an earlier version would have put its temporary file.
+        let taken = dir.join(format!(".poem.txt.minigrep-{}-0", process::id()));
+        fs::write(&taken, "someone else's").unwrap();
+
+        write_atomically(&path, "new").unwrap();
+
+        assert_eq!("new", fs::read_to_string(&path).unwrap());
+        assert_eq!("someone else's", fs::read_to_string(&taken).unwrap());
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_a_symlink_replace_its_target() {
+        let dir = temp_path("symlink");
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.txt");
+        let link = dir.join("link.txt");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomically(&link, "new").unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!("new", fs::read_to_string(&target).unwrap());
+        assert_eq!("new", fs::read_to_string(&link).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
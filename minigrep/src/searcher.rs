@@ -0,0 +1,539 @@
+//! The search itself, for embedding minigrep in other tools.
+//!
+//! A [`Searcher`] finds the lines a [`Matcher`] selects and reports them,
+//! with any context around them, to a [`Sink`]. Nothing is printed, so a
+//! sink can collect, count or format the results however it likes:
+//!
+//! ```
+//! use minigrep::matcher::CaseInsensitive;
+//! use minigrep::{Match, Searcher, Sink};
+//!
+//! struct Count(usize);
+//!
+//! impl Sink for Count {
+//!     fn matched(&mut self, m: Match<'_>) -> std::io::Result<()> {
+//!         self.0 += m.spans.len();
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let searcher = Searcher::new(CaseInsensitive::new("to"));
+//! let mut count = Count(0);
+//! searcher.search_text("To be,\nor not to be", &mut count).unwrap();
+//! assert_eq!(2, count.0);
+//! ```
+
+use std::io::{self, BufRead};
+use std::iter;
+use std::ops::Range;
+
+use memchr::{memchr, memchr_iter, memmem, memrchr};
+
+use crate::context::{Context, Line};
+use crate::matcher::{self, Matcher};
+use crate::{trim_newline, Config, Match};
+
+/// Receives what a `Searcher` finds, in order. An error from any method
+/// stops the search and is returned from it.
+///
+/// Any `FnMut(Line<'_>) -> io::Result<()>` is a sink too, taking every
+/// kind of line as a [`Line`].
+pub trait Sink {
+    /// A selected line.
+    fn matched(&mut self, m: Match<'_>) -> io::Result<()>;
+
+    /// A line of context before or after a selected one.
+    fn context(&mut self, line_number: usize, line: &str) -> io::Result<()> {
+        let _ = (line_number, line);
+        Ok(())
+    }
+
+    /// Lines were skipped between two groups of context.
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(Line<'_>) -> io::Result<()>> Sink for F {
+    fn matched(&mut self, m: Match<'_>) -> io::Result<()> {
+        self(Line::Match(m))
+    }
+
+    fn context(&mut self, line_number: usize, line: &str) -> io::Result<()> {
+        self(Line::Context { line_number, line })
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self(Line::Break)
+    }
+}
+
+/// Finds the lines a matcher selects, and the context around them.
+pub struct Searcher<M> {
+    matcher: M,
+    invert_match: bool,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+    multiline: bool,
+}
+
+impl Searcher<Box<dyn Matcher + Send + Sync>> {
+    /// A searcher that does what `config` asks for.
+    pub fn from_config(config: &Config) -> Self {
+        Searcher::new(matcher::from_config(config))
+            .invert_match(config.invert_match)
+            .context(config.before_context, config.after_context)
+            .max_count(config.max_count)
+            .multiline(config.multiline)
+    }
+}
+
+impl<M: Matcher> Searcher<M> {
+    /// A searcher that selects the lines `matcher` matches, with no
+    /// context.
+    pub fn new(matcher: M) -> Searcher<M> {
+        Searcher {
+            matcher,
+            invert_match: false,
+            before_context: 0,
+            after_context: 0,
+            max_count: None,
+            multiline: false,
+        }
+    }
+
+    /// Select the lines that don't match instead, like `-v`.
+    pub fn invert_match(mut self, invert_match: bool) -> Searcher<M> {
+        self.invert_match = invert_match;
+        self
+    }
+
+    /// Report up to `before` lines before each selected line and `after`
+    /// lines after it as context, like `-B` and `-A`.
+    pub fn context(mut self, before: usize, after: usize) -> Searcher<M> {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    /// Stop after `max_count` selected lines, and any context after the
+    /// last of them, like `-m`.
+    pub fn max_count(mut self, max_count: Option<usize>) -> Searcher<M> {
+        self.max_count = max_count;
+        self
+    }
+
+    /// Search the whole text at once rather than a line at a time, so a
+    /// match can span lines, like `-U`. A match is reported as one selected
+    /// line holding every line it touches, separated by `\n`.
+    pub fn multiline(mut self, multiline: bool) -> Searcher<M> {
+        self.multiline = multiline;
+        self
+    }
+
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    /// The match spans in `line` if it's selected, or `None` if not. A line
+    /// selected because it doesn't match has no spans.
+    pub fn select(&self, line: &str) -> Option<Vec<Range<usize>>> {
+        match (self.matcher.is_match(line), self.invert_match) {
+            (true, false) => Some(self.matcher.spans(line)),
+            (false, true) => Some(Vec::new()),
+            _ => None,
+        }
+    }
+
+    /// Search `reader` one line at a time, passing what it finds to `sink`
+    /// as soon as it is known, and return the number of selected lines.
+    ///
+    /// Only the current line and up to `before` lines of context are held
+    /// in memory, so this works on logs of any size and on pipes that never
+    /// end, such as `tail -f app.log | minigrep ERROR`. A `multiline`
+    /// search is the exception: it reads all of `reader` first.
+    pub fn search_reader(
+        &self,
+        mut reader: impl BufRead,
+        sink: &mut impl Sink,
+    ) -> io::Result<usize> {
+        if self.multiline {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            return self.search_multiline(&text, sink);
+        }
+
+        let mut context = Context::new(self.before_context, self.after_context);
+        let mut emit = forward(sink);
+        let mut buffer = String::new();
+        let mut line_number = 0;
+        let mut byte_offset = 0;
+        let mut count = 0;
+
+        while !self.finished(count, &context) {
+            buffer.clear();
+            let read = reader.read_line(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+
+            line_number += 1;
+            let line = trim_newline(&buffer);
+
+            match self.select(line).filter(|_| !self.at_max(count)) {
+                Some(spans) => {
+                    count += 1;
+                    let m = Match {
+                        line_number,
+                        byte_offset,
+                        line,
+                        spans,
+                    };
+                    context.matched(m, &mut emit)?;
+                }
+                None => context.unmatched(line_number, line, &mut emit)?,
+            }
+
+            byte_offset += read;
+        }
+
+        Ok(count)
+    }
+
+    /// Search `text`, which is all in memory, such as a memory-mapped file,
+    /// the same way `search_reader` would.
+    ///
+    /// A matcher for a single case-sensitive literal, without context or
+    /// `invert_match`, takes a fast path: the whole of `text` is scanned for
+    /// it with a vectorized substring search, and only the lines around
+    /// each hit are split out and counted, so the lines in between are never
+    /// looked at one by one.
+    pub fn search_text(
+        &self,
+        text: &str,
+        sink: &mut impl Sink,
+    ) -> io::Result<usize> {
+        let query = match self.matcher.as_literal() {
+            Some(query)
+                if !self.invert_match
+                    && self.before_context == 0
+                    && self.after_context == 0
+                    // A match has to fit within one line, without its
+                    // ending.
+                    && !query.is_empty()
+                    && !query.contains(['\n', '\r']) =>
+            {
+                query
+            }
+            _ if self.multiline => return self.search_multiline(text, sink),
+            _ => return self.search_reader(text.as_bytes(), sink),
+        };
+
+        let bytes = text.as_bytes();
+        let finder = memmem::Finder::new(query);
+        let mut line_number = 1;
+        // Where the newlines before `line_number` have been counted up to.
+        let mut counted = 0;
+        let mut position = 0;
+        let mut count = 0;
+
+        while let Some(found) = finder
+            .find(&bytes[position..])
+            .filter(|_| !self.at_max(count))
+        {
+            let hit = position + found;
+            let start = memrchr(b'\n', &bytes[..hit]).map_or(0, |i| i + 1);
+            let end =
+                memchr(b'\n', &bytes[hit..]).map_or(bytes.len(), |i| hit + i);
+
+            line_number += memchr_iter(b'\n', &bytes[counted..start]).count();
+            counted = start;
+
+            let line = trim_newline(&text[start..end]);
+            count += 1;
+            sink.matched(Match {
+                line_number,
+                byte_offset: start,
+                line,
+                spans: self.matcher.spans(line),
+            })?;
+
+            position = end + 1;
+            if position >= bytes.len() {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Search all of `text` at once for `multiline`.
+    ///
+    /// Each group of matches that share a line, or that one ends on the
+    /// line the next starts on, is one selected line made of every line
+    /// they touch. With `invert_match`, the lines outside every group are
+    /// selected one at a time.
+    fn search_multiline(
+        &self,
+        text: &str,
+        sink: &mut impl Sink,
+    ) -> io::Result<usize> {
+        let mut context = Context::new(self.before_context, self.after_context);
+        let mut emit = forward(sink);
+        let mut groups = self.groups(text).peekable();
+        let bytes = text.as_bytes();
+        let mut line_number = 1;
+        let mut position = 0;
+        let mut count = 0;
+
+        while position < text.len() && !self.finished(count, &context) {
+            let group = groups
+                .next_if(|(lines, _)| lines.start == position)
+                .filter(|_| !self.at_max(count));
+
+            match group {
+                Some((lines, spans)) if !self.invert_match => {
+                    let line = trim_newline(&text[lines.clone()]);
+                    let touched = line.matches('\n').count() + 1;
+                    count += 1;
+                    let m = Match {
+                        line_number,
+                        byte_offset: position,
+                        line,
+                        spans,
+                    };
+                    context.matched(m, &mut emit)?;
+                    line_number += touched;
+                    position = lines.end + 1;
+                }
+                Some((lines, _)) => {
+                    for line in text[lines.clone()].split('\n') {
+                        let line = trim_newline(line);
+                        context.unmatched(line_number, line, &mut emit)?;
+                        line_number += 1;
+                    }
+                    position = lines.end + 1;
+                }
+                None => {
+                    let end = memchr(b'\n', &bytes[position..])
+                        .map_or(text.len(), |i| position + i);
+                    let line = trim_newline(&text[position..end]);
+
+                    if self.invert_match && !self.at_max(count) {
+                        count += 1;
+                        let m = Match {
+                            line_number,
+                            byte_offset: position,
+                            line,
+                            spans: Vec::new(),
+                        };
+                        context.matched(m, &mut emit)?;
+                    } else {
+                        context.unmatched(line_number, line, &mut emit)?;
+                    }
+
+                    line_number += 1;
+                    position = end + 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// The matches in `text`, in groups that share lines: the byte range
+    /// of the whole lines a group touches, without the last one's line
+    /// ending, and the spans of its matches within those lines.
+    fn groups<'t>(
+        &'t self,
+        text: &'t str,
+    ) -> impl Iterator<Item = (Range<usize>, Vec<Range<usize>>)> + 't {
+        let bytes = text.as_bytes();
+        // Where to look for the next match; past the end once there can't
+        // be one.
+        let mut position = 0;
+        let next = move |position: &mut usize| {
+            if *position > text.len() {
+                return None;
+            }
+            let span = self.matcher.find_at(text, *position)?;
+            // Step over one character after an empty match, so it can't
+            // repeat.
+            *position = match text[span.end..].chars().next() {
+                _ if !span.is_empty() => span.end,
+                Some(c) => span.end + c.len_utf8(),
+                None => text.len() + 1,
+            };
+            Some(span)
+        };
+
+        iter::from_fn(move || {
+            let first = next(&mut position)?;
+            let start =
+                memrchr(b'\n', &bytes[..first.start]).map_or(0, |i| i + 1);
+            let mut end = line_end(bytes, &first);
+            let mut spans = vec![first];
+
+            // Matches starting on a line this group already touches join it.
+            while let Some(span) = next(&mut position) {
+                if span.start > end {
+                    position = span.start;
+                    break;
+                }
+                end = end.max(line_end(bytes, &span));
+                spans.push(span);
+            }
+            position = position.max(end + 1);
+
+            let len = trim_newline(&text[start..end]).len();
+            let spans = spans
+                .into_iter()
+                .map(|span| {
+                    (span.start - start).min(len)..(span.end - start).min(len)
+                })
+                .filter(|span| !span.is_empty())
+                .collect();
+            Some((start..end, spans))
+        })
+    }
+
+    /// Whether `count` selected lines is as many as `max_count` allows.
+    fn at_max(&self, count: usize) -> bool {
+        self.max_count.is_some_and(|max| count >= max)
+    }
+
+    /// Whether the search can stop: every line `max_count` allows has been
+    /// selected, and the context after the last of them has been output.
+    fn finished(&self, count: usize, context: &Context) -> bool {
+        self.at_max(count) && !context.after_pending()
+    }
+}
+
+/// Pass each kind of `Line` to the `Sink` method for it.
+fn forward(
+    sink: &mut impl Sink,
+) -> impl FnMut(Line<'_>) -> io::Result<()> + '_ {
+    |line| match line {
+        Line::Match(m) => sink.matched(m),
+        Line::Context { line_number, line } => sink.context(line_number, line),
+        Line::Break => sink.context_break(),
+    }
+}
+
+/// Where the last line `span` touches ends in `bytes`, before its line
+/// ending. A span that ends with a newline only touches the line that
+/// newline ends.
+fn line_end(bytes: &[u8], span: &Range<usize>) -> usize {
+    let from = if bytes[span.clone()].ends_with(b"\n") {
+        span.end - 1
+    } else {
+        span.end
+    };
+    memchr(b'\n', &bytes[from..]).map_or(bytes.len(), |i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{Literal, Regex};
+
+    /// Collects the line numbers of every match and context line.
+    #[derive(Default)]
+    struct Numbers {
+        matched: Vec<usize>,
+        context: Vec<usize>,
+        breaks: usize,
+    }
+
+    impl Sink for Numbers {
+        fn matched(&mut self, m: Match<'_>) -> io::Result<()> {
+            self.matched.push(m.line_number);
+            Ok(())
+        }
+
+        fn context(&mut self, line_number: usize, _: &str) -> io::Result<()> {
+            self.context.push(line_number);
+            Ok(())
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.breaks += 1;
+            Ok(())
+        }
+    }
+
+    const CONTENTS: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+
+    #[test]
+    fn custom_sink() {
+        let regex = regex::Regex::new("^(two|six)$").unwrap();
+        let searcher = Searcher::new(Regex::new(regex)).context(1, 0);
+        let mut numbers = Numbers::default();
+
+        let count = searcher.search_reader(CONTENTS.as_bytes(), &mut numbers);
+
+        assert_eq!(2, count.unwrap());
+        assert_eq!(vec![2, 6], numbers.matched);
+        assert_eq!(vec![1, 5], numbers.context);
+        assert_eq!(1, numbers.breaks);
+    }
+
+    #[test]
+    fn inverted_search() {
+        let searcher = Searcher::new(Literal::new("e")).invert_match(true);
+        let mut numbers = Numbers::default();
+
+        searcher.search_text(CONTENTS, &mut numbers).unwrap();
+
+        assert_eq!(vec![2, 4, 6], numbers.matched);
+        assert!(numbers.context.is_empty());
+    }
+
+    #[test]
+    fn multiline_matches_are_grouped_by_line() {
+        let regex = regex::Regex::new(r"o\nt|ee|si|x\n").unwrap();
+        let searcher = Searcher::new(Regex::new(regex)).multiline(true);
+        let mut found = Vec::new();
+
+        let count = searcher.search_text(CONTENTS, &mut |line: Line<'_>| {
+            if let Line::Match(m) = line {
+                found.push((m.line_number, m.line.to_string(), m.spans));
+            }
+            Ok(())
+        });
+
+        assert_eq!(2, count.unwrap());
+        assert_eq!(
+            vec![
+                (2, "two\nthree".to_string(), vec![2..5, 7..9]),
+                (6, "six".to_string(), vec![0..2, 2..3]),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn max_count_stops_the_search() {
+        let searcher = Searcher::new(Literal::new("e")).max_count(Some(2));
+        let mut numbers = Numbers::default();
+
+        searcher.search_text(CONTENTS, &mut numbers).unwrap();
+
+        assert_eq!(vec![1, 3], numbers.matched);
+    }
+
+    #[test]
+    fn sink_errors_stop_the_search() {
+        let searcher = Searcher::new(Literal::new("o"));
+        let mut seen = 0;
+
+        let result = searcher.search_text(CONTENTS, &mut |_: Line<'_>| {
+            seen += 1;
+            Err(io::Error::other("full"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(1, seen);
+    }
+}
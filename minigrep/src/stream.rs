@@ -0,0 +1,193 @@
+use std::io::{self, BufRead};
+
+use crate::context::Line;
+use crate::{Config, Searcher};
+
+/// Search `reader` one line at a time, passing each line of output to
+/// `emit` as soon as it is known, and return the number of selected lines.
+///
+/// Only the current line and up to `config.before_context` earlier lines
+/// are held in memory, so this works on logs of any size and on pipes that
+/// never end, such as `tail -f app.log | minigrep ERROR`.
+pub fn search_reader(
+    config: &Config,
+    reader: impl BufRead,
+    mut emit: impl FnMut(Line<'_>) -> io::Result<()>,
+) -> io::Result<usize> {
+    Searcher::from_config(config).search_reader(reader, &mut emit)
+}
+
+/// Search `text`, which is all in memory, such as a memory-mapped file,
+/// the same way `search_reader` would, but with a fast path for a single
+/// case-sensitive literal; see [`Searcher::search_text`].
+pub fn search_text(
+    config: &Config,
+    text: &str,
+    mut emit: impl FnMut(Line<'_>) -> io::Result<()>,
+) -> io::Result<usize> {
+    Searcher::from_config(config).search_text(text, &mut emit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `search_reader` emits, as owned `(kind, line number, text)`.
+    fn output(args: &[&str], contents: &str) -> Vec<(char, usize, String)> {
+        let config = crate::testing::config(args);
+        let mut lines = Vec::new();
+
+        search_reader(&config, contents.as_bytes(), |line| {
+            lines.push(match line {
+                Line::Match(m) => (':', m.line_number, m.line.to_string()),
+                Line::Context { line_number, line } => {
+                    ('-', line_number, line.to_string())
+                }
+                Line::Break => ('|', 0, String::new()),
+            });
+            Ok(())
+        })
+        .unwrap();
+
+        lines
+    }
+
+    fn line(kind: char, number: usize, text: &str) -> (char, usize, String) {
+        (kind, number, text.to_string())
+    }
+
+    const CONTENTS: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n";
+
+    #[test]
+    fn separate_windows_get_a_break() {
+        assert_eq!(
+            vec![
+                line('-', 1, "one"),
+                line(':', 2, "two"),
+                line('|', 0, ""),
+                line('-', 6, "six"),
+                line(':', 7, "seven"),
+            ],
+            output(&["-E", "-B1", "^(two|seven)$", "-"], CONTENTS)
+        );
+    }
+
+    #[test]
+    fn overlapping_windows_are_merged() {
+        assert_eq!(
+            vec![
+                line('-', 1, "one"),
+                line(':', 2, "two"),
+                line('-', 3, "three"),
+                line(':', 4, "four"),
+                line('-', 5, "five"),
+            ],
+            output(&["-E", "-C1", "^(two|four)$", "-"], CONTENTS)
+        );
+    }
+
+    #[test]
+    fn windows_stop_at_the_edges() {
+        assert_eq!(
+            vec![
+                line(':', 1, "one"),
+                line('-', 2, "two"),
+                line('-', 3, "three"),
+                line('|', 0, ""),
+                line('-', 6, "six"),
+                line('-', 7, "seven"),
+                line(':', 8, "eight"),
+            ],
+            output(&["-E", "-C2", "^(one|eight)$", "-"], CONTENTS)
+        );
+    }
+
+    #[test]
+    fn no_breaks_without_context() {
+        assert_eq!(
+            vec![line(':', 2, "two"), line(':', 7, "seven")],
+            output(&["-E", "^(two|seven)$", "-"], CONTENTS)
+        );
+    }
+
+    #[test]
+    fn max_count_keeps_the_context_after_the_last_match() {
+        assert_eq!(
+            vec![line(':', 1, "one"), line('-', 2, "two")],
+            output(&["-m1", "-A1", "o", "-"], CONTENTS)
+        );
+        assert_eq!(
+            vec![line(':', 2, "two"), line(':', 4, "four")],
+            output(&["--max-count=2", "-w", "-E", "t.o|f..r", "-"], CONTENTS)
+        );
+        assert!(output(&["-m0", "o", "-"], CONTENTS).is_empty());
+    }
+
+    #[test]
+    fn multiline_matches_span_lines() {
+        assert_eq!(
+            vec![
+                line('-', 1, "one"),
+                line(':', 2, "two\nthree"),
+                line('-', 4, "four"),
+                line('|', 0, ""),
+                line('-', 6, "six"),
+                line(':', 7, "seven\neight"),
+            ],
+            output(&["-U", "-C1", "-E", "o\\nth|n\\ne", "-"], CONTENTS)
+        );
+        assert_eq!(
+            vec![line(':', 1, "one"), line(':', 4, "four")],
+            output(
+                &["-U", "-v", "-E", "^t\\w+$", "-"],
+                "one\ntwo\nthree\nfour"
+            )
+        );
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        assert_eq!(vec![line(':', 2, "b")], output(&["b", "-"], "a\r\nb"));
+    }
+
+    #[test]
+    fn text_search_agrees_with_reader_search() {
+        let contents = "to be\nor not\r\n\nto be to\nt\ntoo";
+        let searches: &[&[&str]] = &[
+            &["to", "-"],
+            &["o", "-"],
+            &["-v", "to", "-"],
+            &["-C1", "not", "-"],
+            &["-i", "TO", "-"],
+            &["", "-"],
+            &["absent", "-"],
+            &["-m2", "to", "-"],
+            &["-w", "to", "-"],
+            &["-x", "to be", "-"],
+            &["-U", "-E", "be\\nor|not\\r\\n\\nto", "-"],
+        ];
+
+        for args in searches {
+            let config = crate::testing::config(args);
+            let mut from_text = Vec::new();
+            let mut from_reader = Vec::new();
+
+            let count = search_text(&config, contents, |line| {
+                from_text.push(format!("{:?}", line));
+                Ok(())
+            })
+            .unwrap();
+            search_reader(&config, contents.as_bytes(), |line| {
+                from_reader.push(format!("{:?}", line));
+                Ok(())
+            })
+            .unwrap();
+
+            assert_eq!(from_reader, from_text, "searching with {:?}", args);
+            assert_eq!(
+                from_text.iter().filter(|l| l.starts_with("Match")).count(),
+                count
+            );
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Fixtures shared by the unit tests.
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use crate::Config;
+
+/// The command line `minigrep ARGS`, as `Config::new` takes it.
+pub(crate) fn args(args: &[&str]) -> impl Iterator<Item = String> {
+    let mut all = vec!["minigrep".to_string()];
+    all.extend(args.iter().map(|s| s.to_string()));
+    all.into_iter()
+}
+
+/// The search `minigrep ARGS` asks for.
+pub(crate) fn config(args: &[&str]) -> Config {
+    Config::new(self::args(args)).unwrap()
+}
+
+/// A path in the temporary directory for the test file or directory called
+/// `name`, which tests running at the same time in other processes won't
+/// also use. Nothing is created there.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("minigrep-{}-{}", process::id(), name))
+}
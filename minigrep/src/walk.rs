@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::types::{Types, TypesBuilder};
+use ignore::WalkBuilder;
+
+use crate::Error;
+
+/// Which files a directory walk turns up. By default, like ripgrep, files
+/// and directories named by `.gitignore`, `.ignore` and git's own exclude
+/// files are skipped, and so is anything hidden.
+pub struct Walker {
+    /// `--glob` patterns, in order; a leading `!` excludes what it matches.
+    globs: Vec<String>,
+    /// `--type` names, such as `rust` or `md`.
+    types: Vec<String>,
+    hidden: bool,
+    ignore_files: bool,
+}
+
+impl Walker {
+    /// A walker that keeps only files matching `globs` and `types`, the
+    /// `--glob` and `--type` values. Either one being empty keeps
+    /// everything it would otherwise filter. `hidden` includes hidden
+    /// files, and `ignore_files` turns the ignore rules on.
+    pub fn new(
+        globs: &[String],
+        types: &[String],
+        hidden: bool,
+        ignore_files: bool,
+    ) -> Result<Walker, Error> {
+        let walker = Walker {
+            globs: globs.to_vec(),
+            types: types.to_vec(),
+            hidden,
+            ignore_files,
+        };
+        // Globs are matched relative to each directory searched, so the
+        // filters are built for each walk; building them once here catches
+        // bad ones before anything is searched.
+        walker.overrides(Path::new("."))?;
+        walker.file_types()?;
+
+        Ok(walker)
+    }
+
+    /// Collect every regular file below `root` that passes the filters,
+    /// depth first and sorted by name so the output order is stable between
+    /// runs.
+    ///
+    /// Entries that can't be read are reported on stderr and skipped, and
+    /// symbolic links are not followed, which keeps a link cycle from
+    /// turning into an endless walk.
+    pub fn files(&self, root: &Path) -> Vec<PathBuf> {
+        let overrides = self
+            .overrides(root)
+            .expect("globs were checked in Walker::new");
+        let types = self
+            .file_types()
+            .expect("types were checked in Walker::new");
+
+        let walk = WalkBuilder::new(root)
+            .hidden(!self.hidden)
+            .ignore(self.ignore_files)
+            .git_ignore(self.ignore_files)
+            .git_global(self.ignore_files)
+            .git_exclude(self.ignore_files)
+            .parents(self.ignore_files)
+            // Honor a .gitignore even in a tree that isn't a git checkout.
+            .require_git(false)
+            .follow_links(false)
+            .overrides(overrides)
+            .types(types)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+
+        let mut files = Vec::new();
+        for entry in walk {
+            match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_some_and(|t| t.is_file()) {
+                        files.push(entry.into_path());
+                    }
+                }
+                Err(e) => eprintln!("minigrep: {}", e),
+            }
+        }
+
+        files
+    }
+
+    /// The `--glob` patterns, in the order they were given.
+    pub fn globs(&self) -> &[String] {
+        &self.globs
+    }
+
+    /// The `--type` names.
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    /// Whether hidden files are searched.
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Whether `.gitignore` and `.ignore` files are honored.
+    pub fn ignore_files(&self) -> bool {
+        self.ignore_files
+    }
+
+    fn overrides(&self, root: &Path) -> Result<Override, Error> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.globs {
+            builder.add(glob).map_err(Error::InvalidFilter)?;
+        }
+        builder.build().map_err(Error::InvalidFilter)
+    }
+
+    fn file_types(&self) -> Result<Types, Error> {
+        let mut builder = TypesBuilder::new();
+        builder.add_defaults();
+        for name in &self.types {
+            builder.select(name);
+        }
+        builder.build().map_err(Error::InvalidFilter)
+    }
+}
+
+impl Default for Walker {
+    /// No globs or types, skipping hidden and ignored files.
+    fn default() -> Walker {
+        Walker::new(&[], &[], false, true).expect("no filters to get wrong")
+    }
+}
+
+/// Collect every file below `root` with the default filters; see
+/// [`Walker::files`].
+pub fn files(root: &Path) -> Vec<PathBuf> {
+    Walker::default().files(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+    use std::fs;
+
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = temp_path(name);
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    fn strings(globs: &[&str]) -> Vec<String> {
+        globs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn walks_nested_directories() {
+        let root =
+            tree("walk", &[("a.txt", ""), ("b/c/d.txt", ""), ("b/e.txt", "")]);
+
+        let found = files(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            vec![
+                root.join("a.txt"),
+                root.join("b/c/d.txt"),
+                root.join("b/e.txt")
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn ignored_and_hidden_files_are_skipped() {
+        let root = tree(
+            "walk-ignore",
+            &[
+                (".gitignore", "target/\n*.log\n"),
+                (".ignore", "notes.md\n"),
+                (".hidden/a.txt", ""),
+                ("app.log", ""),
+                ("notes.md", ""),
+                ("src/main.rs", ""),
+                ("target/debug/out.txt", ""),
+            ],
+        );
+
+        let default = files(&root);
+        let everything =
+            Walker::new(&[], &[], true, false).unwrap().files(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("src/main.rs")], default);
+        assert_eq!(
+            vec![
+                root.join(".gitignore"),
+                root.join(".hidden/a.txt"),
+                root.join(".ignore"),
+                root.join("app.log"),
+                root.join("notes.md"),
+                root.join("src/main.rs"),
+                root.join("target/debug/out.txt"),
+            ],
+            everything
+        );
+    }
+
+    #[test]
+    fn globs_and_types_filter_files() {
+        let root = tree(
+            "walk-globs",
+            &[
+                ("README.md", ""),
+                ("src/lib.rs", ""),
+                ("src/main.rs", ""),
+                ("vendor/dep.rs", ""),
+            ],
+        );
+
+        let rust = Walker::new(&[], &strings(&["rust"]), false, true)
+            .unwrap()
+            .files(&root);
+        let globbed =
+            Walker::new(&strings(&["*.rs", "!vendor/**"]), &[], false, true)
+                .unwrap()
+                .files(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            vec![
+                root.join("src/lib.rs"),
+                root.join("src/main.rs"),
+                root.join("vendor/dep.rs")
+            ],
+            rust
+        );
+        assert_eq!(
+            vec![root.join("src/lib.rs"), root.join("src/main.rs")],
+            globbed
+        );
+    }
+
+    #[test]
+    fn bad_filters_are_errors() {
+        assert!(matches!(
+            Walker::new(&[], &strings(&["klingon"]), false, true),
+            Err(Error::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            Walker::new(&strings(&["a[b"]), &[], false, true),
+            Err(Error::InvalidFilter(_))
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::Error;
+
+pub const USAGE: &str = "\
+Usage: web_server [OPTIONS]
+
+Serve the files below a directory over HTTP, handling several connections
+at once on a fixed pool of threads. A request for a directory gets its
+index.html, and a request for anything that isn't there gets the
+directory's 404.html with a 404 status.
+
+On SIGINT or SIGTERM, web_server stops accepting connections and waits for
+the open ones to be answered before exiting. A second signal exits at once.
+
+Options:
+  -a, --address ADDR      Listen on the IP address ADDR (default: 127.0.0.1)
+  -p, --port PORT         Listen on PORT; 0 picks any free port
+                          (default: 7878)
+  -j, --threads N         Handle up to N connections at once (default: 4)
+  -r, --root DIR          Serve the files below DIR (default: public)
+  -t, --timeout SECS      Give up on a client that sends or takes nothing
+                          for SECS seconds (default: 10)
+  -s, --shutdown-timeout SECS
+                          Wait at most SECS seconds for open connections
+                          when shutting down (default: 30)
+  -h, --help              Print this help and exit
+  -V, --version           Print the version and exit
+";
+
+pub struct Config {
+    /// The IP address to listen on.
+    pub address: IpAddr,
+    /// The port to listen on; 0 lets the operating system pick one.
+    pub port: u16,
+    /// How many threads handle connections.
+    pub threads: usize,
+    /// The directory whose files are served, also called the document
+    /// root. Request paths are resolved below it.
+    pub root: PathBuf,
+    /// How long reading a request or writing a response can stall before
+    /// the connection is given up on.
+    pub timeout: Duration,
+    /// How long shutting down waits for connections already accepted.
+    pub shutdown_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 7878,
+            threads: 4,
+            root: PathBuf::from("public"),
+            timeout: Duration::from_secs(10),
+            shutdown_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Config {
+    /// The address and port together.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+/// What the command line asks web_server to do.
+pub enum Command {
+    Serve(Config),
+    Help,
+    Version,
+}
+
+impl Command {
+    /// Parse `args`, the whole command line.
+    pub fn parse(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<Command, Error> {
+        args.next();
+
+        let mut config = Config::default();
+
+        while let Some(arg) = args.next() {
+            let (name, inline) = if let Some(long) = arg.strip_prefix("--") {
+                match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                }
+            } else if let Some(short) = arg.strip_prefix('-') {
+                // A short option's value may be attached, as in `-p8080`.
+                match short.char_indices().nth(1) {
+                    Some((i, _)) => (&short[..i], Some(short[i..].to_string())),
+                    None => (short, None),
+                }
+            } else {
+                return Err(Error::UnexpectedArgument(arg));
+            };
+
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| Error::MissingValue(flag(name)))
+            };
+
+            match name {
+                "a" | "address" => {
+                    config.address =
+                        parse_value(name, value()?, "an IP address")?
+                }
+                "p" | "port" => {
+                    config.port = parse_value(name, value()?, "a port number")?
+                }
+                "j" | "threads" => {
+                    config.threads =
+                        parse_value(name, value()?, "a number above 0")?;
+                    if config.threads == 0 {
+                        return Err(Error::InvalidValue {
+                            option: flag(name),
+                            value: "0".to_string(),
+                            expected: "a number above 0",
+                        });
+                    }
+                }
+                "r" | "root" => config.root = PathBuf::from(value()?),
+                "t" | "timeout" => {
+                    let seconds = parse_value(
+                        name,
+                        value()?,
+                        "a number of seconds above 0",
+                    )?;
+                    if seconds == 0 {
+                        return Err(Error::InvalidValue {
+                            option: flag(name),
+                            value: "0".to_string(),
+                            expected: "a number of seconds above 0",
+                        });
+                    }
+                    config.timeout = Duration::from_secs(seconds);
+                }
+                "s" | "shutdown-timeout" => {
+                    config.shutdown_timeout = Duration::from_secs(parse_value(
+                        name,
+                        value()?,
+                        "a number of seconds",
+                    )?)
+                }
+                "h" | "help" | "V" | "version" if inline.is_some() => {
+                    return Err(Error::UnexpectedValue(flag(name)));
+                }
+                "h" | "help" => return Ok(Command::Help),
+                "V" | "version" => return Ok(Command::Version),
+                _ => return Err(Error::UnknownOption(arg)),
+            }
+        }
+
+        Ok(Command::Serve(config))
+    }
+}
+
+fn flag(name: &str) -> String {
+    if name.chars().count() == 1 {
+        format!("-{}", name)
+    } else {
+        format!("--{}", name)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(
+    name: &str,
+    value: String,
+    expected: &'static str,
+) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::InvalidValue {
+        option: flag(name),
+        value,
+        expected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, Error> {
+        let mut all = vec!["web_server".to_string()];
+        all.extend(args.iter().map(|s| s.to_string()));
+        Command::parse(all.into_iter())
+    }
+
+    fn serve(args: &[&str]) -> Config {
+        match parse(args) {
+            Ok(Command::Serve(config)) => config,
+            _ => panic!("expected a config from {:?}", args),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let config = serve(&[]);
+
+        assert_eq!("127.0.0.1:7878", config.socket_addr().to_string());
+        assert_eq!(4, config.threads);
+        assert_eq!(PathBuf::from("public"), config.root);
+        assert_eq!(Duration::from_secs(10), config.timeout);
+        assert_eq!(Duration::from_secs(30), config.shutdown_timeout);
+    }
+
+    #[test]
+    fn every_option() {
+        let config =
+            serve(&["-a", "0.0.0.0", "--port=8080", "-j8", "--root", "site"]);
+
+        assert_eq!("0.0.0.0:8080", config.socket_addr().to_string());
+        assert_eq!(8, config.threads);
+        assert_eq!(PathBuf::from("site"), config.root);
+
+        let config = serve(&["--address", "::1", "-p", "0", "-s0"]);
+        assert_eq!("[::1]:0", config.socket_addr().to_string());
+        assert_eq!(Duration::ZERO, config.shutdown_timeout);
+
+        let config = serve(&["--shutdown-timeout=5", "-t", "2"]);
+        assert_eq!(Duration::from_secs(5), config.shutdown_timeout);
+        assert_eq!(Duration::from_secs(2), config.timeout);
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(parse(&["--help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["-p", "1", "-V"]), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert!(matches!(
+            parse(&["--port", "http"]),
+            Err(Error::InvalidValue {
+                expected: "a port number",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&["-p", "70000"]),
+            Err(Error::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["--threads=0"]),
+            Err(Error::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["--shutdown-timeout", "soon"]),
+            Err(Error::InvalidValue {
+                expected: "a number of seconds",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&["--timeout=0"]),
+            Err(Error::InvalidValue {
+                expected: "a number of seconds above 0",
+                ..
+            })
+        ));
+        assert!(matches!(parse(&["-a"]), Err(Error::MissingValue(_))));
+        assert!(matches!(
+            parse(&["--help=me"]),
+            Err(Error::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            parse(&["--verbose"]),
+            Err(Error::UnknownOption(_))
+        ));
+        assert!(matches!(
+            parse(&["public"]),
+            Err(Error::UnexpectedArgument(_))
+        ));
+    }
+}
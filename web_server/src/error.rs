@@ -0,0 +1,78 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+use crate::pool::PoolCreationError;
+
+/// Everything that can go wrong in web_server, from parsing the command
+/// line to listening for connections.
+#[derive(Debug)]
+pub enum Error {
+    /// An option web_server doesn't know, as it was typed.
+    UnknownOption(String),
+    /// An option that needs a value came last, without one.
+    MissingValue(String),
+    /// A flag was given a value with `--flag=value`.
+    UnexpectedValue(String),
+    /// An argument that isn't an option; web_server takes none.
+    UnexpectedArgument(String),
+    /// An option's value couldn't be used.
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// The server couldn't listen on its address.
+    Bind {
+        address: SocketAddr,
+        source: io::Error,
+    },
+    /// The threads to handle connections couldn't be started.
+    Pool(PoolCreationError),
+    /// The handler for SIGINT and SIGTERM couldn't be set.
+    Signal(ctrlc::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownOption(option) => {
+                write!(f, "unknown option '{}'", option)
+            }
+            Error::MissingValue(option) => {
+                write!(f, "option '{}' needs a value", option)
+            }
+            Error::UnexpectedValue(option) => {
+                write!(f, "option '{}' doesn't take a value", option)
+            }
+            Error::UnexpectedArgument(arg) => {
+                write!(f, "unexpected argument '{}'", arg)
+            }
+            Error::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value '{}' for '{}': expected {}",
+                value, option, expected
+            ),
+            Error::Bind { address, source } => {
+                write!(f, "can't listen on {}: {}", address, source)
+            }
+            Error::Pool(e) => write!(f, "{}", e),
+            Error::Signal(e) => write!(f, "can't handle signals: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bind { source, .. } => Some(source),
+            Error::Pool(e) => Some(e),
+            Error::Signal(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! A handler that answers with the files below a document root.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::response::{HTML, TEXT};
+use crate::{Params, Request, Response};
+
+/// A handler serving the files below `root`, for a route such as
+/// `/*path` or `/static/*path`: the `path` parameter names the file. A
+/// missing file gets root/404.html, or the built-in page if there's none.
+pub fn files(
+    root: impl Into<PathBuf>,
+) -> impl Fn(&Request, &Params) -> Response + Send + Sync + 'static {
+    let root = root.into();
+
+    move |request, params| {
+        let path = params
+            .get("path")
+            .unwrap_or_else(|| request.path().trim_start_matches('/'));
+
+        let found = file_for(&root, path).and_then(|path| {
+            Some((fs::read(&path).ok()?, content_type(&path)))
+        });
+        match found {
+            Some((contents, content_type)) => Response::new(200)
+                .header("Content-Type", content_type)
+                .body(contents),
+            None => match fs::read(root.join("404.html")) {
+                Ok(contents) => Response::html(contents).status(404),
+                Err(_) => Response::not_found(),
+            },
+        }
+    }
+}
+
+/// The file below `root` that the relative `path` names, if it exists. A
+/// directory stands for its index.html. Paths that would lead out of
+/// `root` aren't followed.
+fn file_for(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut file = root.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => file.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if file.is_dir() {
+        file.push("index.html");
+    }
+    file.is_file().then_some(file)
+}
+
+/// The `Content-Type` for a file, going by its extension.
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => HTML,
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => TEXT,
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("public")
+    }
+
+    #[test]
+    fn paths_map_to_files() {
+        let root = public();
+
+        assert_eq!(Some(root.join("index.html")), file_for(&root, ""));
+        assert_eq!(Some(root.join("404.html")), file_for(&root, "./404.html"));
+        assert_eq!(None, file_for(&root, "missing.html"));
+    }
+
+    #[test]
+    fn only_paths_inside_the_root_are_served() {
+        let root = public().join("..").join("public");
+
+        assert_eq!(None, file_for(&root, "../Cargo.toml"));
+        assert_eq!(None, file_for(&root, "/etc/passwd"));
+        assert_eq!(None, file_for(&root, "a/../../Cargo.toml"));
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(
+            "text/html; charset=utf-8",
+            content_type(Path::new("a.HTML"))
+        );
+        assert_eq!("image/png", content_type(Path::new("logo.png")));
+        assert_eq!(
+            "application/octet-stream",
+            content_type(Path::new("Makefile"))
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! The multithreaded web server from the last chapter of the book, as a
+//! library: a [`ThreadPool`], a [`Request`] parser, a [`Router`] that
+//! picks a handler for each request, and a [`Server`] that puts them
+//! together. The binary serves the files below a document root with
+//! [`files`].
+
+use std::process;
+
+pub mod config;
+mod error;
+mod files;
+pub mod pool;
+pub mod request;
+pub mod response;
+pub mod router;
+pub mod server;
+
+pub use config::{Command, Config, USAGE};
+pub use error::Error;
+pub use files::files;
+pub use pool::{ExecuteError, PoolCreationError, ThreadPool};
+pub use request::{Headers, ParseError, Request, Version};
+pub use response::Response;
+pub use router::{ParamError, Params, Router};
+pub use server::{handle_connection, Server, ShutdownHandle};
+
+/// Serve the files below `config.root` on `config`'s address until SIGINT
+/// or SIGTERM, then shut down gracefully. A second signal exits at once.
+pub fn run(config: Config) -> Result<(), Error> {
+    let router = Router::new().get("/*path", files(&config.root));
+    let server = Server::bind(&config, router)?;
+
+    let shutdown = server.shutdown_handle();
+    ctrlc::set_handler(move || {
+        if shutdown.is_shutting_down() {
+            eprintln!("web_server: stopping without waiting");
+            process::exit(130);
+        }
+        println!("Shutting down; signal again to stop at once");
+        shutdown.shutdown();
+    })
+    .map_err(Error::Signal)?;
+
+    if let Ok(address) = server.local_addr() {
+        println!("Listening on http://{}", address);
+    }
+    server.run();
+
+    Ok(())
+}
@@ -1,3 +1,28 @@
+use std::env;
+use std::process;
+
+use web_server::{Command, USAGE};
+
 fn main() {
-    println!("Hello, world!");
+    let config = match Command::parse(env::args()) {
+        Ok(Command::Serve(config)) => config,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("web_server {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("Problem parsing arguments: {}", err);
+            eprint!("\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = web_server::run(config) {
+        eprintln!("Application error: {}", e);
+        process::exit(1);
+    }
 }
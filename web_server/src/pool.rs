@@ -0,0 +1,376 @@
+//! A fixed set of threads that run jobs sent to them, so a server can
+//! handle several connections at once without spawning a thread for each.
+
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+impl ThreadPool {
+    /// The most threads a pool may have.
+    pub const MAX_SIZE: usize = 1024;
+
+    /// Create a new ThreadPool.
+    ///
+    /// The size is the number of threads in the pool.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the size is zero or over
+    /// `MAX_SIZE`, or if a thread can't be started. Use `build` to get
+    /// an error instead.
+    pub fn new(size: usize) -> ThreadPool {
+        ThreadPool::build(size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a new ThreadPool of `size` threads, named `worker-0`,
+    /// `worker-1` and so on, or say why it can't be done. Threads already
+    /// started when one fails are stopped again.
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPool::build_with(size, Worker::new)
+    }
+
+    /// Like `build`, but starting each worker with `spawn`, so tests can
+    /// make it fail.
+    fn build_with<S>(
+        size: usize,
+        mut spawn: S,
+    ) -> Result<ThreadPool, PoolCreationError>
+    where
+        S: FnMut(usize, Arc<Mutex<mpsc::Receiver<Job>>>) -> io::Result<Worker>,
+    {
+        if size == 0 {
+            return Err(PoolCreationError::ZeroSize);
+        }
+        if size > ThreadPool::MAX_SIZE {
+            return Err(PoolCreationError::TooLarge(size));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        // Built in place, so dropping it on an error joins the workers
+        // started so far.
+        let mut pool = ThreadPool {
+            workers: Vec::with_capacity(size),
+            sender: Some(sender),
+        };
+
+        for id in 0..size {
+            let worker = spawn(id, Arc::clone(&receiver))
+                .map_err(|source| PoolCreationError::Spawn { id, source })?;
+            pool.workers.push(worker);
+        }
+
+        Ok(pool)
+    }
+
+    /// Run `f` on the next thread that's free. Jobs wait their turn if
+    /// every thread is busy. If every thread has stopped, `f` is dropped
+    /// without running and an error is returned.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+
+        // The sender is only taken by `shutdown` and `drop`, which both
+        // consume the pool, so it's still here.
+        match &self.sender {
+            Some(sender) => sender.send(job).map_err(|_| ExecuteError),
+            None => Err(ExecuteError),
+        }
+    }
+
+    /// The number of threads in the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Like dropping the pool, but give up waiting for the jobs already
+    /// sent once `timeout` has passed. Returns how many threads were still
+    /// busy then; they're left to finish on their own.
+    pub fn shutdown(mut self, timeout: Duration) -> usize {
+        drop(self.sender.take());
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            for worker in &mut self.workers {
+                if worker.thread.as_ref().is_some_and(|t| t.is_finished()) {
+                    worker.thread.take().unwrap().join().unwrap();
+                }
+            }
+
+            let busy =
+                self.workers.iter().filter(|w| w.thread.is_some()).count();
+            if busy == 0 || Instant::now() >= deadline {
+                // Dropping the handles that are left detaches their threads.
+                for worker in &mut self.workers {
+                    worker.thread.take();
+                }
+                return busy;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    /// Let every job already sent finish, then stop the threads.
+    fn drop(&mut self) {
+        // Closing the channel ends each worker's loop once it's empty.
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(
+        id: usize,
+        receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+    ) -> io::Result<Worker> {
+        let builder = thread::Builder::new().name(format!("worker-{}", id));
+        let thread = builder.spawn(move || loop {
+            let message = receiver.lock().unwrap().recv();
+
+            match message {
+                // A job that panics has its message printed by the panic
+                // hook, and the worker carries on with the next one.
+                Ok(job) => {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                }
+                Err(_) => break,
+            }
+        })?;
+
+        Ok(Worker {
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Why a [`ThreadPool`] couldn't be built.
+#[derive(Debug)]
+pub enum PoolCreationError {
+    /// A pool needs at least one thread.
+    ZeroSize,
+    /// More threads than [`ThreadPool::MAX_SIZE`] were asked for.
+    TooLarge(usize),
+    /// The operating system wouldn't start the thread for worker `id`.
+    Spawn { id: usize, source: io::Error },
+}
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolCreationError::ZeroSize => {
+                write!(f, "a thread pool needs at least one thread")
+            }
+            PoolCreationError::TooLarge(size) => write!(
+                f,
+                "a thread pool can have at most {} threads, not {}",
+                ThreadPool::MAX_SIZE,
+                size
+            ),
+            PoolCreationError::Spawn { id, source } => {
+                write!(f, "can't start thread worker-{}: {}", id, source)
+            }
+        }
+    }
+}
+
+/// Why [`ThreadPool::execute`] couldn't take a job: no thread is left to
+/// run it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecuteError;
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "every thread of the pool has stopped")
+    }
+}
+
+impl std::error::Error for ExecuteError {}
+
+impl std::error::Error for PoolCreationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PoolCreationError::Spawn { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn runs_every_job_before_dropping() {
+        let done = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPool::new(3);
+
+        for _ in 0..10 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                done.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        drop(pool);
+
+        assert_eq!(10, done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn jobs_run_at_the_same_time() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        // Each job waits for the other, so this only finishes if both run
+        // at once.
+        for _ in 0..2 {
+            let barrier = Arc::clone(&barrier);
+            let sender = sender.clone();
+            pool.execute(move || {
+                barrier.wait();
+                sender.send(()).unwrap();
+            })
+            .unwrap();
+        }
+
+        assert_eq!(2, pool.size());
+        assert_eq!(2, receiver.iter().take(2).count());
+    }
+
+    #[test]
+    fn shutdown_waits_until_the_timeout() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = mpsc::channel::<()>();
+
+        pool.execute(|| thread::sleep(Duration::from_millis(20)))
+            .unwrap();
+        pool.execute(move || {
+            receiver.recv().unwrap();
+        })
+        .unwrap();
+
+        let start = Instant::now();
+        assert_eq!(1, pool.shutdown(Duration::from_millis(100)));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        sender.send(()).unwrap();
+
+        let pool = ThreadPool::new(2);
+        pool.execute(|| thread::sleep(Duration::from_millis(20)))
+            .unwrap();
+        assert_eq!(0, pool.shutdown(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn threads_are_named_after_their_worker() {
+        let pool = ThreadPool::build(2).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        for _ in 0..2 {
+            let barrier = Arc::clone(&barrier);
+            let sender = sender.clone();
+            pool.execute(move || {
+                barrier.wait();
+                let name = thread::current().name().map(str::to_string);
+                sender.send(name).unwrap();
+            })
+            .unwrap();
+        }
+
+        let mut names: Vec<_> = receiver.iter().take(2).flatten().collect();
+        names.sort();
+        assert_eq!(vec!["worker-0", "worker-1"], names);
+    }
+
+    #[test]
+    fn bad_sizes_are_errors() {
+        assert!(matches!(
+            ThreadPool::build(0),
+            Err(PoolCreationError::ZeroSize)
+        ));
+        assert!(matches!(
+            ThreadPool::build(ThreadPool::MAX_SIZE + 1),
+            Err(PoolCreationError::TooLarge(1025))
+        ));
+    }
+
+    #[test]
+    fn workers_started_before_one_fails_are_stopped() {
+        let mut receivers = Vec::new();
+        let result = ThreadPool::build_with(4, |id, receiver| {
+            receivers.push(Arc::downgrade(&receiver));
+            if id == 2 {
+                return Err(io::Error::other("no memory"));
+            }
+            Worker::new(id, receiver)
+        });
+
+        let error = result.err().unwrap();
+        assert!(matches!(error, PoolCreationError::Spawn { id: 2, .. }));
+        assert_eq!("can't start thread worker-2: no memory", error.to_string());
+        assert_eq!(
+            "no memory",
+            std::error::Error::source(&error).unwrap().to_string()
+        );
+
+        // Every worker has been joined, so none holds the channel anymore.
+        assert_eq!(3, receivers.len());
+        assert!(receivers.iter().all(|r| r.upgrade().is_none()));
+    }
+
+    #[test]
+    fn a_panicking_job_doesnt_stop_its_thread() {
+        let pool = ThreadPool::build(1).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        pool.execute(|| panic!("job failed on purpose")).unwrap();
+        pool.execute(move || sender.send(()).unwrap()).unwrap();
+
+        assert_eq!(Ok(()), receiver.recv());
+        drop(pool);
+    }
+
+    #[test]
+    fn jobs_for_a_pool_without_threads_are_errors() {
+        let (sender, _) = mpsc::channel();
+        let pool = ThreadPool {
+            workers: Vec::new(),
+            sender: Some(sender),
+        };
+
+        assert_eq!(Err(ExecuteError), pool.execute(|| {}));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one thread")]
+    fn zero_threads_panics() {
+        ThreadPool::new(0);
+    }
+}
@@ -0,0 +1,678 @@
+//! Reading an HTTP/1.1 request off a connection: the request line, the
+//! header fields, and a body sized by `Content-Length` or sent in chunks.
+
+use std::fmt;
+use std::io::{self, prelude::*};
+
+/// The longest line the head of a request may have.
+const MAX_LINE: usize = 8 * 1024;
+/// The most header fields a request may have, and the most trailer fields.
+const MAX_HEADERS: usize = 100;
+/// The biggest body a request may have.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// The HTTP versions web_server speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Version::Http10 => write!(f, "HTTP/1.0"),
+            Version::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
+
+/// The header fields of a request, in the order they came. Names are
+/// compared without regard to case, and a name may appear more than once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    /// The value of the first field called `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The values of every field called `name`, in order.
+    pub fn get_all<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Every field as a name and a value, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The comma-separated elements of every field called `name`, as one
+    /// list.
+    fn list<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.get_all(name)
+            .flat_map(|value| value.split(','))
+            .map(|element| element.trim_matches([' ', '\t']))
+            .filter(|element| !element.is_empty())
+    }
+}
+
+/// One request, read in full.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    method: String,
+    target: String,
+    version: Version,
+    headers: Headers,
+    body: Vec<u8>,
+    trailers: Headers,
+}
+
+impl Request {
+    /// Read a request from `reader`, stopping right after its body.
+    pub fn read<R: BufRead>(reader: &mut R) -> Result<Request, ParseError> {
+        // A client may send empty lines before the request line.
+        let mut request_line = String::new();
+        for _ in 0..MAX_HEADERS {
+            request_line = match head_line(reader)? {
+                Some(line) => line,
+                None => return Err(ParseError::Closed),
+            };
+            if !request_line.is_empty() {
+                break;
+            }
+        }
+        let (method, target, version) = parse_request_line(&request_line)?;
+
+        let mut headers = Headers::default();
+        read_fields(reader, &mut headers)?;
+
+        let (body, trailers) = read_body(reader, &headers)?;
+
+        Ok(Request {
+            method: method.to_string(),
+            target: target.to_string(),
+            version,
+            headers,
+            body,
+            trailers,
+        })
+    }
+
+    /// The method, such as `GET` or `POST`, as it was sent.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The request target: the path along with any query string.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The path of the target, without the query string.
+    pub fn path(&self) -> &str {
+        match self.target.split_once('?') {
+            Some((path, _)) => path,
+            None => &self.target,
+        }
+    }
+
+    /// The query string of the target, without the `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, query)| query)
+    }
+
+    /// The `name=value` pairs of the query string, in order. A pair
+    /// without `=` has an empty value. Nothing is percent-decoded.
+    pub fn query_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// The value of the first header field called `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// The body, with any chunked encoding taken off.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The fields of a chunked body's trailer. They are kept apart from
+    /// the headers so that they can't change how the request was framed or
+    /// routed.
+    pub fn trailers(&self) -> &Headers {
+        &self.trailers
+    }
+}
+
+/// Why a request couldn't be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Reading from the connection failed.
+    Io(io::Error),
+    /// The connection closed before a request started.
+    Closed,
+    /// The connection closed part way through a request.
+    Incomplete,
+    /// The client sent nothing for longer than the read timeout.
+    TimedOut,
+    /// The request line isn't `METHOD TARGET HTTP/x.y`.
+    RequestLine(String),
+    /// The request line names a version other than HTTP/1.0 or HTTP/1.1.
+    Version(String),
+    /// A header field isn't `name: value`.
+    Header(String),
+    /// A `Content-Length` that isn't a single number.
+    ContentLength(String),
+    /// A `Transfer-Encoding` other than `chunked`.
+    TransferEncoding(String),
+    /// Both a `Transfer-Encoding` and a `Content-Length`, which two servers
+    /// could take to frame the body differently.
+    Framing,
+    /// A chunk of a chunked body is malformed.
+    Chunk(String),
+    /// A line of the head is too long, or there are too many fields.
+    HeadTooLarge,
+    /// The body is too big.
+    BodyTooLarge,
+}
+
+impl ParseError {
+    /// The status to answer the request with, for errors that get an
+    /// answer. An I/O error or a closed connection gets none.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ParseError::Io(_) | ParseError::Closed => None,
+            ParseError::TimedOut => Some(408),
+            ParseError::Version(_) => Some(505),
+            ParseError::TransferEncoding(_) => Some(501),
+            ParseError::HeadTooLarge => Some(431),
+            ParseError::BodyTooLarge => Some(413),
+            _ => Some(400),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "can't read request: {}", e),
+            ParseError::Closed => write!(f, "connection closed"),
+            ParseError::Incomplete => {
+                write!(f, "connection closed in the middle of a request")
+            }
+            ParseError::TimedOut => {
+                write!(f, "timed out waiting for a request")
+            }
+            ParseError::RequestLine(line) => {
+                write!(f, "malformed request line '{}'", line)
+            }
+            ParseError::Version(version) => {
+                write!(f, "unsupported version '{}'", version)
+            }
+            ParseError::Header(line) => {
+                write!(f, "malformed header field '{}'", line)
+            }
+            ParseError::ContentLength(value) => {
+                write!(f, "invalid Content-Length '{}'", value)
+            }
+            ParseError::TransferEncoding(value) => {
+                write!(f, "unsupported Transfer-Encoding '{}'", value)
+            }
+            ParseError::Framing => {
+                write!(f, "both Transfer-Encoding and Content-Length given")
+            }
+            ParseError::Chunk(line) => write!(f, "malformed chunk '{}'", line),
+            ParseError::HeadTooLarge => write!(
+                f,
+                "request head has lines over {} bytes or over {} fields",
+                MAX_LINE, MAX_HEADERS
+            ),
+            ParseError::BodyTooLarge => {
+                write!(f, "request body is over {} bytes", MAX_BODY)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => ParseError::Incomplete,
+            // What a read timeout looks like, depending on the platform.
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                ParseError::TimedOut
+            }
+            _ => ParseError::Io(e),
+        }
+    }
+}
+
+/// Read one line of the head, without its line ending. `None` means the
+/// connection was closed before the line started.
+fn head_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, ParseError> {
+    let mut line = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_LINE as u64 + 2)
+        .read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(if line.len() >= MAX_LINE {
+            ParseError::HeadTooLarge
+        } else {
+            ParseError::Incomplete
+        });
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    if line.len() > MAX_LINE {
+        return Err(ParseError::HeadTooLarge);
+    }
+
+    match String::from_utf8(line) {
+        Ok(line) => Ok(Some(line)),
+        Err(e) => Err(ParseError::Header(
+            String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        )),
+    }
+}
+
+fn parse_request_line(line: &str) -> Result<(&str, &str, Version), ParseError> {
+    let malformed = || ParseError::RequestLine(line.to_string());
+
+    let mut parts = line.split(' ');
+    let (method, target, version) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version), None) => {
+                (method, target, version)
+            }
+            _ => return Err(malformed()),
+        };
+
+    if !is_token(method) {
+        return Err(malformed());
+    }
+    let origin_form =
+        target.starts_with('/') && !target.contains(char::is_control);
+    if !origin_form && target != "*" {
+        return Err(malformed());
+    }
+
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        _ => {
+            let number = version.strip_prefix("HTTP/").ok_or_else(malformed)?;
+            let digits: Vec<_> = number.split('.').collect();
+            let well_formed = digits.len() <= 2
+                && digits.iter().all(|d| {
+                    !d.is_empty() && d.bytes().all(|b| b.is_ascii_digit())
+                });
+            if !well_formed {
+                return Err(malformed());
+            }
+            return Err(ParseError::Version(version.to_string()));
+        }
+    };
+
+    Ok((method, target, version))
+}
+
+/// Read header fields up to the empty line that ends them.
+fn read_fields<R: BufRead>(
+    reader: &mut R,
+    headers: &mut Headers,
+) -> Result<(), ParseError> {
+    loop {
+        let line = head_line(reader)?.ok_or(ParseError::Incomplete)?;
+        if line.is_empty() {
+            return Ok(());
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(ParseError::HeadTooLarge);
+        }
+
+        // No whitespace is allowed before the colon, and a line starting
+        // with whitespace would be the obsolete line folding.
+        let field = line
+            .split_once(':')
+            .filter(|(name, _)| is_token(name))
+            .map(|(name, value)| (name, value.trim_matches([' ', '\t'])))
+            .filter(|(_, value)| !value.contains(['\r', '\0']));
+        match field {
+            Some((name, value)) => {
+                headers.fields.push((name.to_string(), value.to_string()))
+            }
+            None => return Err(ParseError::Header(line)),
+        }
+    }
+}
+
+/// Read the body `headers` describe, if any, along with the fields of its
+/// trailer if it is chunked.
+fn read_body<R: BufRead>(
+    reader: &mut R,
+    headers: &Headers,
+) -> Result<(Vec<u8>, Headers), ParseError> {
+    let codings: Vec<_> = headers.list("Transfer-Encoding").collect();
+    if !codings.is_empty() {
+        // A length alongside chunks could be read two ways by two servers,
+        // so it is refused rather than ignored.
+        if headers.get("Content-Length").is_some() {
+            return Err(ParseError::Framing);
+        }
+        let only_chunked =
+            codings.len() == 1 && codings[0].eq_ignore_ascii_case("chunked");
+        if !only_chunked {
+            return Err(ParseError::TransferEncoding(codings.join(", ")));
+        }
+        return read_chunked(reader);
+    }
+
+    let mut length = None;
+    for value in headers.list("Content-Length") {
+        let invalid = || ParseError::ContentLength(value.to_string());
+        if !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let value: usize =
+            value.parse().map_err(|_| ParseError::BodyTooLarge)?;
+        if length.is_some_and(|length| length != value) {
+            return Err(invalid());
+        }
+        length = Some(value);
+    }
+
+    let length = length.unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(ParseError::BodyTooLarge);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok((body, Headers::default()))
+}
+
+fn read_chunked<R: BufRead>(
+    reader: &mut R,
+) -> Result<(Vec<u8>, Headers), ParseError> {
+    let mut body = Vec::new();
+
+    loop {
+        let line = head_line(reader)?.ok_or(ParseError::Incomplete)?;
+        // Chunk extensions after a `;` are allowed and ignored.
+        let size = line.split(';').next().unwrap_or("").trim_end();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::Chunk(line));
+        }
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| ParseError::BodyTooLarge)?;
+        if size == 0 {
+            break;
+        }
+        if body.len() + size > MAX_BODY {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+
+        match head_line(reader)? {
+            Some(end) if end.is_empty() => {}
+            Some(end) => return Err(ParseError::Chunk(end)),
+            None => return Err(ParseError::Incomplete),
+        }
+    }
+
+    let mut trailers = Headers::default();
+    read_fields(reader, &mut trailers)?;
+    Ok((body, trailers))
+}
+
+/// Whether `s` is a token: a method or a field name.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|b| {
+            b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(request: &str) -> Result<Request, ParseError> {
+        Request::read(&mut request.as_bytes())
+    }
+
+    #[test]
+    fn request_line_and_headers() {
+        let request = parse(
+            "GET /search?q=rust&page=2&all HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Accept:text/html\r\n\
+             accept:  text/plain \r\n\
+             \r\n",
+        )
+        .unwrap();
+
+        assert_eq!("GET", request.method());
+        assert_eq!("/search?q=rust&page=2&all", request.target());
+        assert_eq!("/search", request.path());
+        assert_eq!(Some("q=rust&page=2&all"), request.query());
+        assert_eq!(
+            vec![("q", "rust"), ("page", "2"), ("all", "")],
+            request.query_pairs().collect::<Vec<_>>()
+        );
+        assert_eq!(Version::Http11, request.version());
+        assert_eq!(Some("localhost"), request.header("HOST"));
+        assert_eq!(
+            vec!["text/html", "text/plain"],
+            request.headers().get_all("Accept").collect::<Vec<_>>()
+        );
+        assert_eq!(3, request.headers().len());
+        assert!(request.body().is_empty());
+    }
+
+    #[test]
+    fn bare_newlines_and_leading_empty_lines() {
+        let request = parse("\r\n\nPOST /form HTTP/1.0\nX: 1\n\n").unwrap();
+
+        assert_eq!("POST", request.method());
+        assert_eq!(None, request.query());
+        assert_eq!(Version::Http10, request.version());
+        assert_eq!(Some("1"), request.header("x"));
+    }
+
+    #[test]
+    fn body_sized_by_content_length() {
+        let mut input = "PUT /a HTTP/1.1\r\n\
+                         Content-Length: 5\r\n\
+                         Content-Length: 5\r\n\
+                         \r\n\
+                         helloGET"
+            .as_bytes();
+
+        let request = Request::read(&mut input).unwrap();
+
+        assert_eq!(b"hello", request.body());
+        assert_eq!(b"GET", input);
+    }
+
+    #[test]
+    fn chunked_body_with_trailer() {
+        let request = parse(
+            "POST / HTTP/1.1\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             5;name=value\r\nhello\r\n\
+             7\r\n, world\r\n\
+             0\r\n\
+             Expires: never\r\n\
+             \r\n",
+        )
+        .unwrap();
+
+        assert_eq!(b"hello, world", request.body());
+        assert_eq!(Some("never"), request.trailers().get("expires"));
+        assert_eq!(None, request.header("expires"));
+    }
+
+    #[test]
+    fn trailers_cant_change_the_headers() {
+        let request = parse(
+            "POST /users HTTP/1.1\r\n\
+             Host: example.com\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             2\r\nhi\r\n\
+             0\r\n\
+             Content-Length: 999\r\n\
+             Host: evil.example\r\n\
+             \r\n",
+        )
+        .unwrap();
+
+        assert_eq!(b"hi", request.body());
+        assert_eq!(None, request.header("content-length"));
+        assert_eq!(
+            vec!["example.com"],
+            request.headers().get_all("host").collect::<Vec<_>>()
+        );
+        assert_eq!(Some("999"), request.trailers().get("content-length"));
+        assert_eq!(2, request.headers().len());
+    }
+
+    #[test]
+    fn malformed_requests() {
+        let bad_request = |input: &str| match parse(input) {
+            Err(e) => e.status() == Some(400),
+            Ok(_) => false,
+        };
+
+        assert!(bad_request("GET /\r\n\r\n"));
+        assert!(bad_request("GET  / HTTP/1.1\r\n\r\n"));
+        assert!(bad_request("GET / HTTP/1.1 extra\r\n\r\n"));
+        assert!(bad_request("G(E)T / HTTP/1.1\r\n\r\n"));
+        assert!(bad_request("GET index.html HTTP/1.1\r\n\r\n"));
+        assert!(bad_request("GET / FTP/1.1\r\n\r\n"));
+        assert!(bad_request("GET / HTTP/1.1\r\nno colon\r\n\r\n"));
+        assert!(bad_request("GET / HTTP/1.1\r\nName : value\r\n\r\n"));
+        assert!(bad_request("GET / HTTP/1.1\r\nA: 1\r\n folded\r\n\r\n"));
+        assert!(bad_request("GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"));
+        assert!(bad_request(
+            "GET / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n"
+        ));
+        assert!(bad_request(
+            "GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"
+        ));
+        assert!(bad_request(
+            "GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+             3\r\nabcd\r\n0\r\n\r\n"
+        ));
+        assert!(bad_request("GET / HTTP/1.1\r\n"));
+        assert!(bad_request("GET / HTTP/1.1\r\nContent-Length: 4\r\n\r\nab"));
+    }
+
+    #[test]
+    fn a_length_alongside_chunks_is_a_bad_request() {
+        for request in [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\
+             Content-Length: 3\r\n\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 0\r\n\
+             Transfer-Encoding: gzip\r\n\r\n",
+        ] {
+            let error = parse(request).unwrap_err();
+            assert!(matches!(error, ParseError::Framing));
+            assert_eq!(Some(400), error.status());
+        }
+    }
+
+    #[test]
+    fn other_errors_get_their_own_status() {
+        let status = |input: &str| parse(input).unwrap_err().status();
+
+        assert_eq!(None, status(""));
+        assert_eq!(
+            Some(408),
+            ParseError::from(io::Error::from(io::ErrorKind::WouldBlock))
+                .status()
+        );
+        assert_eq!(Some(505), status("GET / HTTP/2.0\r\n\r\n"));
+        assert_eq!(
+            Some(501),
+            status("GET / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n")
+        );
+        assert_eq!(
+            Some(501),
+            status(
+                "GET / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"
+            )
+        );
+        assert_eq!(
+            Some(431),
+            status(&format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE)))
+        );
+        assert_eq!(
+            Some(431),
+            status(&format!(
+                "GET / HTTP/1.1\r\n{}\r\n",
+                "A: 1\r\n".repeat(MAX_HEADERS + 1)
+            ))
+        );
+        assert_eq!(
+            Some(413),
+            status(&format!(
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY + 1
+            ))
+        );
+    }
+}
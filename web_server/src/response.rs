@@ -0,0 +1,192 @@
+//! What a handler answers a request with.
+
+use std::io::{self, prelude::*};
+
+pub(crate) const HTML: &str = "text/html; charset=utf-8";
+pub(crate) const TEXT: &str = "text/plain; charset=utf-8";
+
+/// The page sent with a 404 when nothing better is at hand.
+const NOT_FOUND: &str = "\
+<!DOCTYPE html>
+<html lang=\"en\">
+  <head>
+    <meta charset=\"utf-8\">
+    <title>Not Found</title>
+  </head>
+  <body>
+    <h1>Oops!</h1>
+    <p>Sorry, I don't know what you're asking for.</p>
+  </body>
+</html>
+";
+
+/// A status, header fields and a body, ready to be written to a
+/// connection. `Content-Length` and `Connection: close` are added when it
+/// is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// An empty response with `status`.
+    pub fn new(status: u16) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `200 OK` with `body` as HTML.
+    pub fn html(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200).header("Content-Type", HTML).body(body)
+    }
+
+    /// A `200 OK` with `body` as plain text.
+    pub fn text(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200).header("Content-Type", TEXT).body(body)
+    }
+
+    /// A `404 Not Found` with a short HTML page.
+    pub fn not_found() -> Response {
+        Response::html(NOT_FOUND).status(404)
+    }
+
+    /// Add a header field; an earlier one with the same name is replaced.
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Response {
+        self.body = body.into();
+        self
+    }
+
+    /// The same response with another status.
+    pub fn status(mut self, status: u16) -> Response {
+        self.status = status;
+        self
+    }
+
+    pub fn status_code(&self) -> u16 {
+        self.status
+    }
+
+    /// The value of the header field called `name`.
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Write the whole response to `out`. A 1xx or 204 response can't
+    /// have a body, so it gets neither the body nor a `Content-Length`.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let bodiless = self.status < 200 || self.status == 204;
+
+        let mut head =
+            format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        if !bodiless {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+
+        out.write_all(head.as_bytes())?;
+        if !bodiless {
+            out.write_all(&self.body)?;
+        }
+        out.flush()
+    }
+}
+
+/// The reason phrase that goes with `status` in the status line.
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "CREATED",
+        204 => "NO CONTENT",
+        301 => "MOVED PERMANENTLY",
+        302 => "FOUND",
+        304 => "NOT MODIFIED",
+        400 => "BAD REQUEST",
+        403 => "FORBIDDEN",
+        404 => "NOT FOUND",
+        405 => "METHOD NOT ALLOWED",
+        408 => "REQUEST TIMEOUT",
+        413 => "CONTENT TOO LARGE",
+        431 => "REQUEST HEADER FIELDS TOO LARGE",
+        500 => "INTERNAL SERVER ERROR",
+        501 => "NOT IMPLEMENTED",
+        503 => "SERVICE UNAVAILABLE",
+        505 => "HTTP VERSION NOT SUPPORTED",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_status_line_headers_and_body() {
+        let response = Response::text("hello")
+            .header("X-Test", "1")
+            .header("content-type", "text/x-greeting");
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Length: 5\r\n\
+             X-Test: 1\r\n\
+             content-type: text/x-greeting\r\n\
+             Connection: close\r\n\
+             \r\n\
+             hello",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn no_content_has_no_length() {
+        for status in [204, 101] {
+            let mut out = Vec::new();
+            Response::new(status)
+                .body("ignored")
+                .write_to(&mut out)
+                .unwrap();
+
+            assert_eq!(
+                format!(
+                    "HTTP/1.1 {} {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    reason(status)
+                ),
+                String::from_utf8(out).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn status_and_accessors() {
+        let response = Response::html("<p>gone</p>").status(404);
+
+        assert_eq!(404, response.status_code());
+        assert_eq!(Some(HTML), response.header_value("Content-Type"));
+        assert_eq!(b"<p>gone</p>", response.contents());
+        assert_eq!("METHOD NOT ALLOWED", reason(405));
+    }
+}
@@ -0,0 +1,382 @@
+//! Sending each request to the handler registered for its method and path.
+//!
+//! A path pattern is matched a segment at a time. A plain segment matches
+//! only itself, `:name` matches any one non-empty segment, and `*name`,
+//! which can only come last, matches the rest of the path.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::response::TEXT;
+use crate::{Request, Response};
+
+type Handler = Box<dyn Fn(&Request, &Params) -> Response + Send + Sync>;
+
+/// Handlers, each registered for a method and a path pattern.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    method: String,
+    pattern: Pattern,
+    handler: Handler,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Register `handler` for requests with `method` and a path matching
+    /// `pattern`. When several routes match, the first one registered
+    /// wins.
+    ///
+    /// # Panics
+    ///
+    /// The `route` function will panic if `pattern` doesn't start with
+    /// `/`, has a parameter without a name, or has a wildcard anywhere but
+    /// at the end.
+    pub fn route<F>(mut self, method: &str, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method: method.to_string(),
+            pattern: Pattern::parse(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Register `handler` for `GET` requests to `pattern`.
+    pub fn get<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("GET", pattern, handler)
+    }
+
+    /// Register `handler` for `POST` requests to `pattern`.
+    pub fn post<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("POST", pattern, handler)
+    }
+
+    /// Register `handler` for `PUT` requests to `pattern`.
+    pub fn put<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("PUT", pattern, handler)
+    }
+
+    /// Register `handler` for `DELETE` requests to `pattern`.
+    pub fn delete<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("DELETE", pattern, handler)
+    }
+
+    /// Answer `request` with the handler of the first route that matches
+    /// it. A path no route matches gets a 404, and a path that only
+    /// matches routes for other methods gets a 405 listing them.
+    pub fn handle(&self, request: &Request) -> Response {
+        let mut allowed: Vec<&str> = Vec::new();
+
+        for route in &self.routes {
+            let Some(params) = route.pattern.matches(request.path()) else {
+                continue;
+            };
+            if route.method == request.method() {
+                return (route.handler)(request, &params);
+            }
+            if !allowed.contains(&route.method.as_str()) {
+                allowed.push(&route.method);
+            }
+        }
+
+        if allowed.is_empty() {
+            return Response::not_found();
+        }
+        let allowed = allowed.join(", ");
+        Response::new(405)
+            .header("Allow", &allowed)
+            .header("Content-Type", TEXT)
+            .body(format!(
+                "{} isn't allowed here; try {}\n",
+                request.method(),
+                allowed
+            ))
+    }
+}
+
+/// A path pattern, split into segments.
+#[derive(Debug, PartialEq, Eq)]
+struct Pattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Pattern {
+        let Some(rest) = pattern.strip_prefix('/') else {
+            panic!("path pattern '{}' doesn't start with '/'", pattern);
+        };
+
+        let parts: Vec<_> = rest.split('/').collect();
+        let mut segments = Vec::with_capacity(parts.len());
+        for (i, part) in parts.iter().enumerate() {
+            let segment = if let Some(name) = part.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(
+                    i == parts.len() - 1,
+                    "wildcard '{}' isn't at the end of '{}'",
+                    part,
+                    pattern
+                );
+                Segment::Wildcard(name.to_string())
+            } else {
+                segments.push(Segment::Literal(part.to_string()));
+                continue;
+            };
+
+            assert!(
+                part.len() > 1,
+                "parameter without a name in '{}'",
+                pattern
+            );
+            segments.push(segment);
+        }
+
+        Pattern { segments }
+    }
+
+    /// The parameters of `path`, if it matches.
+    fn matches(&self, path: &str) -> Option<Params> {
+        let mut parts = path.strip_prefix('/')?.split('/');
+        let mut params = Params::default();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = parts.next().filter(|part| !part.is_empty())?;
+                    params.pairs.push((name.clone(), value.to_string()));
+                }
+                Segment::Wildcard(name) => {
+                    let rest: Vec<_> = parts.by_ref().collect();
+                    params.pairs.push((name.clone(), rest.join("/")));
+                }
+            }
+        }
+
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+}
+
+/// The values a request's path gave a route's `:name` and `*name`
+/// segments, as they were sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    pairs: Vec<(String, String)>,
+}
+
+impl Params {
+    /// The value of the parameter called `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of the parameter called `name`, parsed as a `T`.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<T, ParamError> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| ParamError::Missing(name.to_string()))?;
+
+        value.parse().map_err(|_| ParamError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// Why a path parameter couldn't be had.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    /// The route has no parameter with this name.
+    Missing(String),
+    /// The value isn't of the type asked for.
+    Invalid { name: String, value: String },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Missing(name) => {
+                write!(f, "no path parameter called '{}'", name)
+            }
+            ParamError::Invalid { name, value } => {
+                write!(f, "invalid value '{}' for '{}'", value, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// A missing parameter is a mistake in the route, but an invalid one is
+/// the client's.
+impl From<ParamError> for Response {
+    fn from(e: ParamError) -> Response {
+        let status = match e {
+            ParamError::Missing(_) => 500,
+            ParamError::Invalid { .. } => 400,
+        };
+        Response::text(format!("{}\n", e)).status(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, target: &str) -> Request {
+        let request = format!("{} {} HTTP/1.1\r\n\r\n", method, target);
+        Request::read(&mut request.as_bytes()).unwrap()
+    }
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        Pattern::parse(pattern)
+            .matches(path)
+            .map(|params| params.pairs)
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn patterns_match_segment_by_segment() {
+        assert_eq!(pairs(&[]), params("/", "/"));
+        assert_eq!(pairs(&[]), params("/users", "/users"));
+        assert_eq!(None, params("/users", "/users/"));
+        assert_eq!(None, params("/users", "/"));
+        assert_eq!(pairs(&[("id", "7")]), params("/users/:id", "/users/7"));
+        assert_eq!(None, params("/users/:id", "/users/"));
+        assert_eq!(None, params("/users/:id", "/users/7/posts"));
+        assert_eq!(
+            pairs(&[("user", "7"), ("post", "x")]),
+            params("/users/:user/posts/:post", "/users/7/posts/x")
+        );
+        assert_eq!(
+            pairs(&[("path", "css/site.css")]),
+            params("/static/*path", "/static/css/site.css")
+        );
+        assert_eq!(pairs(&[("path", "")]), params("/static/*path", "/static/"));
+        assert_eq!(None, params("/static/*path", "/other/a"));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't at the end")]
+    fn wildcards_come_last() {
+        Pattern::parse("/*path/more");
+    }
+
+    #[test]
+    #[should_panic(expected = "without a name")]
+    fn parameters_have_names() {
+        Pattern::parse("/users/:");
+    }
+
+    #[test]
+    fn typed_parameters() {
+        let params = Pattern::parse("/users/:id/:name")
+            .matches("/users/42/ferris")
+            .unwrap();
+
+        assert_eq!(Ok(42), params.parse::<u32>("id"));
+        assert_eq!(Some("ferris"), params.get("name"));
+        assert_eq!(
+            Err(ParamError::Invalid {
+                name: "name".to_string(),
+                value: "ferris".to_string()
+            }),
+            params.parse::<u32>("name")
+        );
+        assert_eq!(
+            Err(ParamError::Missing("age".to_string())),
+            params.parse::<u32>("age")
+        );
+        assert_eq!(
+            400,
+            Response::from(params.parse::<u32>("name").unwrap_err())
+                .status_code()
+        );
+    }
+
+    #[test]
+    fn dispatch_by_method_and_path() {
+        let router = Router::new()
+            .get("/users/:id", |_, params| {
+                Response::text(format!("user {}", params.get("id").unwrap()))
+            })
+            .put("/users/:id", |_, _| Response::new(204))
+            .post("/users", |request, _| {
+                Response::text(request.body().to_vec()).status(201)
+            })
+            .delete("/users/:id", |_, _| Response::new(204));
+
+        let response = router.handle(&request("GET", "/users/3?full"));
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"user 3", response.contents());
+
+        let response = router.handle(&request("POST", "/users"));
+        assert_eq!(201, response.status_code());
+
+        let response = router.handle(&request("GET", "/nobody"));
+        assert_eq!(404, response.status_code());
+
+        let response = router.handle(&request("PATCH", "/users/3"));
+        assert_eq!(405, response.status_code());
+        assert_eq!(Some("GET, PUT, DELETE"), response.header_value("Allow"));
+    }
+
+    #[test]
+    fn first_matching_route_wins() {
+        let router = Router::new()
+            .get("/users/new", |_, _| Response::text("form"))
+            .get("/users/:id", |_, _| Response::text("user"));
+
+        let response = router.handle(&request("GET", "/users/new"));
+        assert_eq!(b"form", response.contents());
+        let response = router.handle(&request("GET", "/users/1"));
+        assert_eq!(b"user", response.contents());
+    }
+}
@@ -0,0 +1,164 @@
+//! Accepting connections and answering each request with its route's
+//! handler, until told to shut down.
+
+use std::io::{self, BufReader};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::request::ParseError;
+use crate::{Config, Error, Request, Response, Router, ThreadPool};
+
+/// A listening server, ready to `run`.
+pub struct Server {
+    listener: TcpListener,
+    pool: ThreadPool,
+    router: Arc<Router>,
+    timeout: Duration,
+    shutdown_timeout: Duration,
+    /// Where a [`ShutdownHandle`] connects to wake `run`.
+    wake_address: SocketAddr,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Server {
+    /// Start listening on `config`'s address and port, with a pool of
+    /// `config.threads` threads to answer requests with `router`.
+    pub fn bind(config: &Config, router: Router) -> Result<Server, Error> {
+        let address = config.socket_addr();
+        let bound = TcpListener::bind(address)
+            .and_then(|listener| Ok((listener.local_addr()?, listener)));
+        let (mut wake_address, listener) =
+            bound.map_err(|source| Error::Bind { address, source })?;
+
+        // The listener can't be reached at the unspecified address, but it
+        // can at the loopback address of the same kind.
+        match wake_address.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => {
+                wake_address.set_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            }
+            IpAddr::V6(ip) if ip.is_unspecified() => {
+                wake_address.set_ip(IpAddr::V6(Ipv6Addr::LOCALHOST))
+            }
+            _ => {}
+        }
+
+        Ok(Server {
+            listener,
+            wake_address,
+            pool: ThreadPool::build(config.threads).map_err(Error::Pool)?,
+            router: Arc::new(router),
+            timeout: config.timeout,
+            shutdown_timeout: config.shutdown_timeout,
+            stopping: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Where the server is listening, including the port picked for it if
+    /// it was asked for port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// A handle that makes `run` return, for another thread or a signal
+    /// handler.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            address: self.wake_address,
+            stopping: Arc::clone(&self.stopping),
+        }
+    }
+
+    /// Hand each connection to the pool as it arrives, until a
+    /// [`ShutdownHandle`] says to stop. Then stop listening, and wait up
+    /// to the shutdown timeout for the connections already accepted to be
+    /// answered. Problems with a single connection are reported on stderr
+    /// and don't stop the server.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("web_server: can't accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            let router = Arc::clone(&self.router);
+            let timeout = self.timeout;
+            let job = self.pool.execute(move || {
+                if let Err(e) = handle_connection(stream, &router, timeout) {
+                    eprintln!("web_server: {}", e);
+                }
+            });
+            if let Err(e) = job {
+                eprintln!("web_server: can't answer connection: {}", e);
+            }
+        }
+
+        drop(self.listener);
+        let busy = self.pool.shutdown(self.shutdown_timeout);
+        if busy > 0 {
+            eprintln!(
+                "web_server: gave up waiting for {} connection{} after {:?}",
+                busy,
+                if busy == 1 { "" } else { "s" },
+                self.shutdown_timeout
+            );
+        }
+    }
+}
+
+/// Stops a running [`Server`] from elsewhere. Cloning it gives another
+/// handle to the same server.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    address: SocketAddr,
+    stopping: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    /// Make the server stop accepting connections and return from `run`
+    /// once the ones it has are answered. Calling it again does nothing.
+    pub fn shutdown(&self) {
+        if !self.stopping.swap(true, Ordering::SeqCst) {
+            // `run` only looks at the flag when a connection arrives, so
+            // make one. If it fails, the listener is already gone.
+            let _ = TcpStream::connect(self.address);
+        }
+    }
+
+    /// Whether `shutdown` has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+}
+
+/// Read one request from `stream`, answer it with `router`, then close the
+/// connection. A request that can't be parsed is answered with the error's
+/// status instead, and one that doesn't arrive within `timeout` with a 408,
+/// so an idle client can't hold a thread of the pool for long.
+pub fn handle_connection(
+    mut stream: TcpStream,
+    router: &Router,
+    timeout: Duration,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let response = match Request::read(&mut BufReader::new(&stream)) {
+        Ok(request) => router.handle(&request),
+        Err(ParseError::Io(e)) => return Err(e),
+        Err(e) => match e.status() {
+            Some(status) => Response::text(format!("{}\n", e)).status(status),
+            None => return Ok(()),
+        },
+    };
+
+    response.write_to(&mut stream)
+}
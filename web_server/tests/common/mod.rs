@@ -0,0 +1,91 @@
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+use web_server::{Config, Router, Server};
+
+/// A response as the tests look at it.
+pub struct Response {
+    pub status_line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /// The value of the header `name`, ignoring its case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A fresh document root for the test called `name`, holding `files`.
+pub fn document_root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root =
+        env::temp_dir().join(format!("web_server-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    fs::create_dir_all(&root).unwrap();
+
+    root
+}
+
+/// Start a server answering with `router` on a free port on localhost, in
+/// the background for the rest of the test, and return where it listens.
+pub fn serve(router: Router, threads: usize) -> SocketAddr {
+    let config = Config {
+        port: 0,
+        threads,
+        ..Config::default()
+    };
+    let server = Server::bind(&config, router).unwrap();
+    let address = server.local_addr().unwrap();
+
+    thread::spawn(move || server.run());
+    address
+}
+
+/// Send `request` as it is and read the response until the server closes
+/// the connection.
+pub fn send(address: SocketAddr, request: &str) -> Response {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap().to_string();
+    let headers = lines
+        .map(|line| {
+            let (name, value) = line.split_once(": ").unwrap();
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+
+    Response {
+        status_line,
+        headers,
+        body: body.to_string(),
+    }
+}
+
+/// `GET` `path` the way a browser would.
+pub fn get(address: SocketAddr, path: &str) -> Response {
+    send(
+        address,
+        &format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address),
+    )
+}
@@ -0,0 +1,26 @@
+use std::error::Error as _;
+
+use web_server::{
+    Config, Error, PoolCreationError, Router, Server, ThreadPool,
+};
+
+#[test]
+fn a_server_with_too_many_threads_is_an_error() {
+    let config = Config {
+        port: 0,
+        threads: ThreadPool::MAX_SIZE + 1,
+        ..Config::default()
+    };
+
+    let error = Server::bind(&config, Router::new()).err().unwrap();
+
+    assert!(matches!(
+        error,
+        Error::Pool(PoolCreationError::TooLarge(1025))
+    ));
+    assert_eq!(
+        "a thread pool can have at most 1024 threads, not 1025",
+        error.to_string()
+    );
+    assert!(error.source().is_some());
+}
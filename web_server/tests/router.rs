@@ -0,0 +1,77 @@
+use web_server::{files, Response, Router};
+
+mod common;
+
+use common::{document_root, get, send, serve};
+
+fn users() -> Router {
+    Router::new()
+        .get("/users/:id", |_, params| match params.parse::<u32>("id") {
+            Ok(id) => Response::text(format!("user {}", id)),
+            Err(e) => e.into(),
+        })
+        .post("/users", |request, _| {
+            Response::text(request.body().to_vec()).status(201)
+        })
+        .delete("/users/:id", |_, _| Response::new(204))
+}
+
+#[test]
+fn handlers_get_typed_path_parameters() {
+    let address = serve(users(), 2);
+
+    let response = get(address, "/users/42");
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+    assert_eq!("user 42", response.body);
+
+    let response = get(address, "/users/ferris");
+    assert_eq!("HTTP/1.1 400 BAD REQUEST", response.status_line);
+    assert_eq!("invalid value 'ferris' for 'id'\n", response.body);
+}
+
+#[test]
+fn handlers_get_the_request() {
+    let address = serve(users(), 1);
+
+    let response = send(
+        address,
+        "POST /users HTTP/1.1\r\nContent-Length: 6\r\n\r\nferris",
+    );
+
+    assert_eq!("HTTP/1.1 201 CREATED", response.status_line);
+    assert_eq!("ferris", response.body);
+}
+
+#[test]
+fn unknown_paths_get_404() {
+    let address = serve(users(), 1);
+
+    let response = get(address, "/posts");
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+
+    let response = get(address, "/users/1/posts");
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+}
+
+#[test]
+fn other_methods_get_405_with_allow() {
+    let address = serve(users(), 1);
+
+    let response = send(address, "PUT /users/1 HTTP/1.1\r\n\r\n");
+
+    assert_eq!("HTTP/1.1 405 METHOD NOT ALLOWED", response.status_line);
+    assert_eq!(Some("GET, DELETE"), response.header("Allow"));
+}
+
+#[test]
+fn files_under_a_wildcard() {
+    let root = document_root("wildcard", &[("css/site.css", "h1 {}")]);
+    let address = serve(users().get("/static/*path", files(root)), 1);
+
+    let response = get(address, "/static/css/site.css");
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+    assert_eq!("h1 {}", response.body);
+
+    let response = get(address, "/css/site.css");
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+}
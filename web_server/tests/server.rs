@@ -0,0 +1,281 @@
+use std::io::Read;
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use web_server::{files, Config, Response, Router, Server};
+
+mod common;
+
+use common::{document_root, get, send, serve};
+
+/// Start a server for `root` on a free port on localhost, serving its
+/// files in the background for the rest of the test, and return where it
+/// listens.
+fn start(root: PathBuf, threads: usize) -> SocketAddr {
+    serve(Router::new().get("/*path", files(root)), threads)
+}
+
+#[test]
+fn serves_the_index_page() {
+    let root = document_root("index", &[("index.html", "<h1>Hello!</h1>")]);
+    let address = start(root, 2);
+
+    let response = get(address, "/");
+
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+    assert_eq!("<h1>Hello!</h1>", response.body);
+    assert_eq!(Some("15"), response.header("Content-Length"));
+    assert_eq!(
+        Some("text/html; charset=utf-8"),
+        response.header("Content-Type")
+    );
+}
+
+#[test]
+fn serves_files_below_the_root() {
+    let root = document_root(
+        "nested",
+        &[
+            ("css/site.css", "h1 { color: red }"),
+            ("docs/index.html", "docs"),
+        ],
+    );
+    let address = start(root, 2);
+
+    let response = get(address, "/css/site.css");
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+    assert_eq!("h1 { color: red }", response.body);
+    assert_eq!(
+        Some("text/css; charset=utf-8"),
+        response.header("Content-Type")
+    );
+
+    assert_eq!("docs", get(address, "/docs/").body);
+    assert_eq!("docs", get(address, "/docs?page=2").body);
+}
+
+#[test]
+fn missing_files_get_the_404_page() {
+    let root = document_root("missing", &[("404.html", "Oops!")]);
+    let address = start(root, 1);
+
+    let response = get(address, "/nothing-here");
+
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+    assert_eq!("Oops!", response.body);
+}
+
+#[test]
+fn a_root_without_404_html_gets_a_built_in_page() {
+    let root = document_root("bare", &[]);
+    let address = start(root, 1);
+
+    let response = get(address, "/");
+
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+    assert!(response.body.contains("<h1>Oops!</h1>"));
+}
+
+#[test]
+fn nothing_outside_the_root_is_served() {
+    let root = document_root("outside", &[("site/index.html", "inside")]);
+    let secret = root.join("secret.txt");
+    std::fs::write(&secret, "secret").unwrap();
+    let address = start(root.join("site"), 1);
+
+    let response = get(address, "/../secret.txt");
+    assert_eq!("HTTP/1.1 404 NOT FOUND", response.status_line);
+
+    let response = send(address, "POST / HTTP/1.1\r\n\r\n");
+    assert_eq!("HTTP/1.1 405 METHOD NOT ALLOWED", response.status_line);
+    assert_eq!(Some("GET"), response.header("Allow"));
+}
+
+#[test]
+fn handles_many_connections_at_once() {
+    let root = document_root("many", &[("index.html", "hi")]);
+    let address = start(root, 4);
+
+    let clients: Vec<_> = (0..16)
+        .map(|_| thread::spawn(move || get(address, "/")))
+        .collect();
+
+    for client in clients {
+        let response = client.join().unwrap();
+        assert_eq!("HTTP/1.1 200 OK", response.status_line);
+        assert_eq!("hi", response.body);
+    }
+}
+
+#[test]
+fn malformed_requests_get_400() {
+    let root = document_root("malformed", &[("index.html", "hi")]);
+    let address = start(root, 1);
+
+    for request in [
+        "GET /\r\n\r\n",
+        "GET / HTTP/1.1\r\nno colon here\r\n\r\n",
+        "GET / HTTP/1.1\r\nContent-Length: lots\r\n\r\n",
+    ] {
+        let response = send(address, request);
+        assert_eq!("HTTP/1.1 400 BAD REQUEST", response.status_line);
+        assert_eq!(
+            Some("text/plain; charset=utf-8"),
+            response.header("Content-Type")
+        );
+    }
+
+    let response = send(address, "GET / HTTP/3\r\n\r\n");
+    assert_eq!(
+        "HTTP/1.1 505 HTTP VERSION NOT SUPPORTED",
+        response.status_line
+    );
+}
+
+#[test]
+fn requests_with_bodies_are_read_in_full() {
+    let root = document_root("bodies", &[("index.html", "hi")]);
+    let address = start(root, 1);
+
+    let response = send(
+        address,
+        "GET /?from=form HTTP/1.1\r\n\
+         Content-Length: 11\r\n\
+         \r\n\
+         name=ferris",
+    );
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+    assert_eq!("hi", response.body);
+
+    let response = send(
+        address,
+        "GET / HTTP/1.1\r\n\
+         Transfer-Encoding: chunked\r\n\
+         \r\n\
+         4\r\nname\r\n7\r\n=ferris\r\n0\r\n\r\n",
+    );
+    assert_eq!("HTTP/1.1 200 OK", response.status_line);
+}
+
+/// A router whose `/slow` handler says when it has started, then waits to
+/// be told to finish.
+fn slow() -> (Router, mpsc::Receiver<()>, mpsc::Sender<()>) {
+    let (started, on_start) = mpsc::channel();
+    let (finish, on_finish) = mpsc::channel();
+    let on_finish = Mutex::new(on_finish);
+
+    let router = Router::new().get("/slow", move |_, _| {
+        started.send(()).unwrap();
+        let _ = on_finish.lock().unwrap().recv();
+        Response::text("done")
+    });
+    (router, on_start, finish)
+}
+
+/// Bind a server for `router` on a free port on localhost, with the rest
+/// of `config`.
+fn bind(router: Router, config: Config) -> Server {
+    Server::bind(&Config { port: 0, ..config }, router).unwrap()
+}
+
+/// A config with two threads and `shutdown_timeout`.
+fn stopping_after(shutdown_timeout: Duration) -> Config {
+    Config {
+        threads: 2,
+        shutdown_timeout,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn shutdown_lets_requests_in_flight_finish() {
+    let (router, on_start, finish) = slow();
+    let server = bind(router, stopping_after(Duration::from_secs(10)));
+    let address = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+    let running = thread::spawn(move || server.run());
+
+    let client = thread::spawn(move || get(address, "/slow"));
+    on_start.recv().unwrap();
+    handle.shutdown();
+    handle.shutdown();
+    assert!(handle.is_shutting_down());
+
+    thread::sleep(Duration::from_millis(50));
+    assert!(!running.is_finished());
+
+    finish.send(()).unwrap();
+    assert_eq!("done", client.join().unwrap().body);
+    running.join().unwrap();
+
+    assert!(TcpStream::connect(address).is_err());
+}
+
+#[test]
+fn shutdown_gives_up_after_the_timeout() {
+    let (router, on_start, finish) = slow();
+    let server = bind(router, stopping_after(Duration::from_millis(100)));
+    let address = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+    let running = thread::spawn(move || server.run());
+
+    let client = thread::spawn(move || get(address, "/slow"));
+    on_start.recv().unwrap();
+
+    let start = Instant::now();
+    handle.shutdown();
+    running.join().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // The request it gave up on is still answered if the process lives on.
+    finish.send(()).unwrap();
+    assert_eq!("done", client.join().unwrap().body);
+}
+
+#[test]
+fn idle_clients_dont_hold_up_other_requests() {
+    let router = Router::new().get("/", |_, _| Response::text("hi"));
+    let config = Config {
+        threads: 1,
+        timeout: Duration::from_millis(200),
+        ..Config::default()
+    };
+    let server = bind(router, config);
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    // It takes the only thread, and sends nothing.
+    let mut idle = TcpStream::connect(address).unwrap();
+
+    let start = Instant::now();
+    assert_eq!("hi", get(address, "/").body);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let mut response = String::new();
+    idle.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 408 REQUEST TIMEOUT\r\n"));
+}
+
+#[test]
+fn shutdown_doesnt_wait_out_idle_clients() {
+    let router = Router::new().get("/", |_, _| Response::text("hi"));
+    let config = Config {
+        timeout: Duration::from_millis(200),
+        ..stopping_after(Duration::from_secs(30))
+    };
+    let server = bind(router, config);
+    let address = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+    let running = thread::spawn(move || server.run());
+
+    let _idle = TcpStream::connect(address).unwrap();
+    assert_eq!("hi", get(address, "/").body);
+
+    let start = Instant::now();
+    handle.shutdown();
+    running.join().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+}